@@ -1,6 +1,9 @@
 use std::{collections::HashMap, env, str::FromStr};
 
-use lambda_http::{http::request::Parts, Body, Error, Request, Response};
+use lambda_http::{
+    http::{request::Parts, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    Body, Error, Request, Response,
+};
 use redis::Commands;
 
 /// This is the main body for the function.
@@ -25,42 +28,43 @@ pub(crate) async fn function_handler(event: Request) -> Result<Response<Body>, E
         Body::Binary(b) => b,
     };
 
-    let mut hahaha_real_headers = HashMap::new();
-    let mut headers_of_unknown_provenance = HashMap::new();
-    for (k, v) in headers {
-        if let Some(k) = k {
-            let k = k.to_string();
-            let v = v.to_str()?.to_string();
-            if let Some(stripped) = k.strip_prefix("hahaha-") {
-                hahaha_real_headers.insert(stripped.to_string(), v);
-            } else {
-                headers_of_unknown_provenance.insert(k, v);
-            }
-        }
-    }
-    headers_of_unknown_provenance.extend(hahaha_real_headers);
-    // and now we have replaced the clobbered headers from who knows where with the real
-    // ones we hahaha-'d in the client to work around what aws would do to the headers otherwise.
-    let mut headers = headers_of_unknown_provenance;
+    let headers = headers
+        .iter()
+        .map(|(k, v)| Ok((k.to_string(), v.to_str()?.to_string())))
+        .collect::<Result<Vec<_>, Error>>()?;
+    // Restore the headers the client renamed or enveloped to work around what aws would do to them
+    // otherwise, over the ones from who knows where. Repeated headers keep every value.
+    let ddb_proxy_protocol::Decoded {
+        target_uri: proxy_uri,
+        mut headers,
+    } = match ddb_proxy_protocol::decode(headers) {
+        Ok(decoded) => decoded,
+        Err(e) => return Ok(bad_request(&e.to_string())),
+    };
 
     tracing::info!("headers: {headers:?}");
 
     // The target header comes from the AWS SDK and is the api call being made.
     let action = headers
-        .get("x-amz-target")
-        .ok_or("missing x-amz-target header")?
-        .clone();
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("x-amz-target"))
+        .map(|(_, v)| v.clone())
+        .ok_or("missing x-amz-target header")?;
 
-    // The x-uri header is the custom header we added to the request _after it was signed_,
+    // The target uri is from the custom header we added to the request _after it was signed_,
     // as we changed the request's target uri to _this Function_.
-    let proxy_uri = headers.get("x-uri").ok_or("missing x-uri header")?.clone();
-    headers.insert(
+    headers.retain(|(k, _)| !k.eq_ignore_ascii_case("host"));
+    headers.push((
         "host".to_string(),
         lambda_http::http::Uri::from_str(&proxy_uri)?
             .host()
             .unwrap_or_default()
             .to_string(),
-    );
+    ));
+    let headers = match header_map(headers) {
+        Ok(headers) => headers,
+        Err(e) => return Ok(bad_request(&e)),
+    };
 
     let response = match action.as_str() {
         "DynamoDB_20120810.GetItem" => handle_get_item(body, headers, &proxy_uri).await?,
@@ -73,6 +77,33 @@ pub(crate) async fn function_handler(event: Request) -> Result<Response<Body>, E
     Ok(response)
 }
 
+/// The headers to forward, or what is wrong with one the client sent.
+fn header_map(headers: Vec<(String, String)>) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let value = HeaderValue::try_from(value.as_str())
+            .map_err(|e| format!("bad value for header {name}: {e}"))?;
+        let name = HeaderName::try_from(name.as_str())
+            .map_err(|e| format!("bad header name {name}: {e}"))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+/// A DynamoDB style error for a request the client shouldn't have sent, so the sdk reports it.
+fn bad_request(message: &str) -> Response<Body> {
+    tracing::warn!("bad request: {message}");
+    let body = serde_json::json!({
+        "__type": "com.amazon.coral.validate#ValidationException",
+        "message": message,
+    });
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .header("content-type", "application/x-amz-json-1.0")
+        .body(body.to_string().into())
+        .expect("must be able to create response")
+}
+
 /// DynamoDB value type for keys
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum KeyValue {
@@ -123,7 +154,7 @@ impl From<lambda_http::http::Response<Vec<u8>>> for CachedResponse {
 
 async fn handle_get_item(
     body: Vec<u8>,
-    headers: impl IntoIterator<Item = (String, String)>,
+    headers: HeaderMap,
     proxy_uri: &str,
 ) -> Result<lambda_http::http::Response<lambda_http::Body>, Error> {
     #[derive(serde::Deserialize, serde::Serialize, Debug)]
@@ -150,23 +181,13 @@ async fn handle_get_item(
             let hit: Vec<u8> = hit;
             let hit = serde_json::from_slice::<CachedResponse>(&hit)?;
             tracing::info!("Cache hit for {cache_key}");
-            hit.into()
+            with_cache_status(hit.into(), "HIT")
         }
         None => {
             tracing::info!("Cache miss for {cache_key} -> {proxy_uri}");
             let response = reqwest::Client::new()
                 .post(proxy_uri)
-                .headers(
-                    headers
-                        .into_iter()
-                        .map(|(k, v)| {
-                            (
-                                k.try_into().expect("header must be header"),
-                                v.try_into().expect("header value must be header value"),
-                            )
-                        })
-                        .collect(),
-                )
+                .headers(headers)
                 .body(body)
                 .send()
                 .await?;
@@ -185,31 +206,34 @@ async fn handle_get_item(
             };
 
             let _: () = connection.set_ex(&cache_key, serde_json::to_vec(&response)?, 60)?;
-            response.into()
+            with_cache_status(response.into(), "MISS")
         }
     })
 }
 
+/// Tell the client whether the response came from the cache or from DynamoDB.
+/// This is not part of the cached response, so it is set on the way out.
+fn with_cache_status(
+    mut response: lambda_http::http::Response<lambda_http::Body>,
+    status: &'static str,
+) -> lambda_http::http::Response<lambda_http::Body> {
+    response.headers_mut().insert(
+        "x-cache",
+        lambda_http::http::HeaderValue::from_static(status),
+    );
+    response
+}
+
 async fn handle_all_other_ddb_calls(
     action: &str,
     body: Vec<u8>,
-    headers: impl IntoIterator<Item = (String, String)>,
+    headers: HeaderMap,
     proxy_uri: &str,
 ) -> Result<lambda_http::http::Response<lambda_http::Body>, Error> {
     tracing::info!("other action: {action} -> {proxy_uri}");
     let request = reqwest::Client::new()
         .post(proxy_uri)
-        .headers(
-            headers
-                .into_iter()
-                .map(|(k, v)| {
-                    (
-                        k.try_into().expect("header must be header"),
-                        v.try_into().expect("header value must be header value"),
-                    )
-                })
-                .collect(),
-        )
+        .headers(headers)
         .body(body);
     tracing::info!("request: {request:?}");
     let response = request.send().await?;
@@ -227,3 +251,58 @@ async fn handle_all_other_ddb_calls(
     };
     Ok(response.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use lambda_http::{http::StatusCode, Body, Request};
+
+    use super::function_handler;
+
+    async fn status(headers: Vec<(String, String)>) -> StatusCode {
+        let mut request = Request::new(Body::Empty);
+        for (name, value) in headers {
+            request.headers_mut().append(
+                lambda_http::http::HeaderName::try_from(name).expect("test names are valid"),
+                value.try_into().expect("test values are valid"),
+            );
+        }
+        let response = function_handler(request)
+            .await
+            .expect("bad requests get a response");
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn malformed_requests_are_bad_requests() {
+        let target = "https://dynamodb.us-west-2.amazonaws.com/";
+        let with = |name: &str, value: &str| {
+            vec![ddb_proxy_protocol::encode_envelope(
+                target,
+                [
+                    (
+                        "x-amz-target".to_string(),
+                        "DynamoDB_20120810.PutItem".to_string(),
+                    ),
+                    (name.to_string(), value.to_string()),
+                ],
+            )]
+        };
+        assert_eq!(
+            status(with("bad name", "value")).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(with("x-custom", "bad\nvalue")).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(vec![(
+                ddb_proxy_protocol::ENVELOPE_HEADER.to_string(),
+                "not base64".to_string()
+            )])
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(status(vec![]).await, StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,7 +1,19 @@
-use clap::Parser;
+use std::{net::SocketAddr, path::PathBuf};
 
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::{
+    mix::OperationMix,
+    proxy_headers::{AuthSource, ProxyHeader, header_name},
+    ttl::Lifetime,
+};
+
+/// Run a load test. The options configure the load test, unless you use a subcommand.
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     /// what these metrics should be logged as
     #[arg(long)]
     pub scenario: Option<String>,
@@ -11,6 +23,103 @@ pub struct Args {
     /// Request rate limit per second
     #[arg(long, default_value = "4")]
     pub tps: u32,
+    /// Weighted operations to send, like get=90,put=10. get populates missing items with a put
+    #[arg(long, default_value = "get")]
+    pub mix: OperationMix,
+    /// How many times an rmw in the mix starts over after losing the race to another writer
+    #[arg(long, default_value = "5")]
+    pub rmw_max_retries: u32,
+    /// Use strongly consistent reads for rmw in the mix
+    #[arg(long)]
+    pub rmw_consistent_read: bool,
+    /// Items in each tget and twrite transaction in the mix, up to 100
+    #[arg(long, default_value = "4")]
+    pub transaction_items: usize,
+    /// Table for query in the mix. The partition key values come from the item generator
+    #[arg(long, default_value = "users")]
+    pub query_table: String,
+    /// Partition key of --query-table
+    #[arg(long, default_value = "user")]
+    pub query_partition_key: String,
+    /// String sort key of --query-table. Queries only read the sort keys from --query-sort-from
+    /// to --query-sort-to
+    #[arg(long, requires_all = ["query_sort_from", "query_sort_to"])]
+    pub query_sort_key: Option<String>,
+    #[arg(long)]
+    pub query_sort_from: Option<String>,
+    #[arg(long)]
+    pub query_sort_to: Option<String>,
+    /// Items per query page
+    #[arg(long)]
+    pub query_limit: Option<i32>,
+    /// Query in descending sort key order
+    #[arg(long)]
+    pub query_backward: bool,
+    /// Most pages each query follows
+    #[arg(long, default_value = "10")]
+    pub query_max_pages: u32,
+    /// Table for scan in the mix
+    #[arg(long, default_value = "users")]
+    pub scan_table: String,
+    /// Segments each scan in the mix reads in parallel
+    #[arg(long, default_value = "4")]
+    pub scan_segments: i32,
+    /// Items per scan page
+    #[arg(long)]
+    pub scan_limit: Option<i32>,
+    /// Most pages each scan segment follows
+    #[arg(long, default_value = "10")]
+    pub scan_max_pages: u32,
+    /// Items in each bget in the mix, up to 100
+    #[arg(long, default_value = "25")]
+    pub batch_get_items: usize,
+    /// Items in each bwrite in the mix, up to 25
+    #[arg(long, default_value = "25")]
+    pub batch_write_items: usize,
+    /// How many times a batch requests its unprocessed items again before giving up on them
+    #[arg(long, default_value = "5")]
+    pub batch_max_retries: u32,
+    /// Longest wait before the first request for unprocessed items, in milliseconds. The longest
+    /// wait doubles for each request after that
+    #[arg(long, default_value = "50")]
+    pub batch_backoff_ms: u64,
+    /// Statements in each pbatch in the mix, up to 25
+    #[arg(long, default_value = "25")]
+    pub partiql_batch_statements: usize,
+    /// Make the PartiQL SELECTs in the mix strongly consistent
+    #[arg(long)]
+    pub partiql_consistent_read: bool,
+    /// Set this DynamoDB TTL attribute on written items, to when they expire
+    #[arg(long)]
+    pub ttl_attribute: Option<String>,
+    /// Lifetime of written items in seconds with --ttl-attribute: `3600` for exactly an hour,
+    /// `60..3600` for uniformly from a minute to an hour, or `exp:600` for exponentially
+    /// distributed with a 10 minute mean. Negative lifetimes write items that already expired
+    #[arg(long, default_value = "3600", allow_hyphen_values = true)]
+    pub ttl_lifetime: Lifetime,
+    /// Instead of steady load, send rounds of this many simultaneous GetItems for one key that
+    /// isn't cached yet, a thundering herd. --tps and --mix don't apply
+    #[arg(long)]
+    pub herd_size: Option<usize>,
+    /// Seconds between thundering herd rounds
+    #[arg(long, default_value = "10")]
+    pub herd_interval: u64,
+    /// Serve an http api to change the rate and mix, pause, resume and dump stats while running,
+    /// like 127.0.0.1:9092
+    #[arg(long)]
+    pub control_listen: Option<SocketAddr>,
+    /// Run as a worker for --coordinate: serve at this address, like 0.0.0.0:9300, and wait for
+    /// a coordinator to assign this process its share of the load
+    #[arg(long, conflicts_with = "coordinate")]
+    pub worker_listen: Option<SocketAddr>,
+    /// Coordinate a run across --worker-listen processes, like 10.0.0.1:9300,10.0.0.2:9300,
+    /// instead of sending load from this one. --tps and --items are split between the workers,
+    /// they start together and their metrics are merged into one report
+    #[arg(long, value_delimiter = ',', requires = "duration")]
+    pub coordinate: Vec<String>,
+    /// How far ahead the coordinator schedules the synchronized start, in milliseconds
+    #[arg(long, default_value = "2000")]
+    pub start_delay_ms: u64,
     /// Randomness seed to generate items
     #[arg(long, default_value = "31")]
     pub seed: u64,
@@ -20,6 +129,38 @@ pub struct Args {
     /// Item key length
     #[arg(long, default_value = "10")]
     pub item_key_length: usize,
+    /// Split the keyspace into this many disjoint shards, for runs across processes without
+    /// --coordinate
+    #[arg(long, default_value = "1")]
+    pub shard_count: u64,
+    /// The shard of the keyspace this process owns, from 0 to --shard-count - 1
+    #[arg(long, default_value = "0")]
+    pub shard_index: u64,
+    /// Give each thread's worker its own part of this process's keys, like clients with
+    /// sticky sessions
+    #[arg(long)]
+    pub partition_workers: bool,
+    /// Extend each shard by this fraction of its size into the next ones, so neighbors share some
+    /// keys. Applies to worker partitions with --partition-workers, otherwise to process shards
+    #[arg(long, default_value = "0")]
+    pub shard_overlap: f64,
+    /// Write each item this process owns once before the run starts. Overlap doesn't count, so
+    /// processes populating the table together don't write any item twice
+    #[arg(long)]
+    pub populate: bool,
+    /// Concurrent writes while populating
+    #[arg(long, default_value = "64")]
+    pub populate_concurrency: usize,
+    /// Fraction of each shard's keys in the active working set
+    #[arg(long, default_value = "1")]
+    pub working_set: f64,
+    /// Seconds for the working set to move by its own size, so all of its keys have turned over.
+    /// The working set doesn't move if unset
+    #[arg(long)]
+    pub drift_period: Option<f64>,
+    /// What the working set does when it drifts past the last key
+    #[arg(long, value_enum, default_value = "rotate")]
+    pub drift: DriftMode,
     /// service log level
     #[arg(long)]
     pub service_log: Option<String>,
@@ -27,6 +168,23 @@ pub struct Args {
     /// ex: https://api.cache.developer-kenny-dev.preprod.a.momentohq.com/functions/fls/ddbaccelerator
     #[arg(long)]
     pub accelerator_url: Option<String>,
+    /// The header that tells the proxy where the request was signed for. Not for --scenario lambda
+    #[arg(long, default_value = ddb_proxy_protocol::TARGET_URI_HEADER, value_parser = header_name)]
+    pub proxy_target_header: String,
+    /// The header that authorizes requests to the proxy. Not for --scenario lambda
+    #[arg(long, default_value = "x-momento-authorization", value_parser = header_name)]
+    pub proxy_auth_header: String,
+    /// Where --proxy-auth-header's value comes from: env:NAME, file:PATH, command:COMMAND, or none
+    /// for no auth header. Read once, when the run starts
+    #[arg(long, default_value = "env:MOMENTO_AUTH_TOKEN")]
+    pub proxy_auth: AuthSource,
+    /// A static header for the proxy, like "x-api-key: abc". Repeat it for more headers. They are
+    /// added after signing, so they only reach the proxy. Not for --scenario lambda
+    #[arg(long)]
+    pub proxy_header: Vec<ProxyHeader>,
+    /// How --scenario lambda packs the signed request's headers for the lambda
+    #[arg(long, value_enum, default_value = "renamed")]
+    pub lambda_encoding: LambdaEncoding,
     /// The authorization header for sending metrics to an opentelemetry endpoint
     #[arg(long)]
     pub metrics_authorization: Option<String>,
@@ -36,4 +194,121 @@ pub struct Args {
     /// The opentelemetry endpoint to which to send metrics
     #[arg(long)]
     pub metrics_endpoint: Option<String>,
+    /// Serve prometheus metrics at http://<address>/metrics, like 0.0.0.0:9091
+    #[arg(long)]
+    pub prometheus_listen: Option<SocketAddr>,
+    /// Write a time series and a summary of the run to this directory, as json and csv
+    #[arg(long)]
+    pub results_dir: Option<PathBuf>,
+    /// Seconds per time series interval in --results-dir
+    #[arg(long, default_value = "1")]
+    pub results_interval: u64,
+    /// Show a live dashboard in the terminal instead of logging to it
+    #[arg(long)]
+    pub tui: bool,
+    /// Where logs go while --tui is showing the dashboard
+    #[arg(long, default_value = "ddb-load-tester.log")]
+    pub tui_log_file: PathBuf,
+    /// How long to run, in seconds. Runs until interrupted if unset
+    #[arg(long)]
+    pub duration: Option<u64>,
+    /// On-demand price in USD per million read request units, for the cost estimate
+    #[arg(long, default_value = "0.125")]
+    pub read_request_unit_price: f64,
+    /// On-demand price in USD per million write request units, for the cost estimate
+    #[arg(long, default_value = "0.625")]
+    pub write_request_unit_price: f64,
+    /// Timeout for a whole sdk operation in milliseconds, including all of its retries
+    #[arg(long)]
+    pub operation_timeout_ms: Option<u64>,
+    /// Timeout for each attempt of an sdk operation in milliseconds
+    #[arg(long)]
+    pub operation_attempt_timeout_ms: Option<u64>,
+    /// How the sdk retries failed requests. Uses AWS_RETRY_MODE or the profile's retry_mode if
+    /// unset, and standard without either
+    #[arg(long, value_enum)]
+    pub retry_mode: Option<RetryPolicy>,
+    /// Maximum attempts per operation, including the first one. Uses AWS_MAX_ATTEMPTS, the
+    /// profile's max_attempts or the sdk default if unset
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Compare the results of 2 or more runs against the first, and flag regressions.
+    /// Exits with status 1 if any run regressed
+    Compare(CompareArgs),
+    /// Serve a local, in-memory stand-in for DynamoDB, to run against with
+    /// AWS_ENDPOINT_URL=http://<listen>
+    Local(LocalArgs),
+}
+
+#[derive(clap::Args)]
+pub struct CompareArgs {
+    /// --results-dir directories of the runs to compare. The first one is the baseline
+    #[arg(required = true, num_args = 2..)]
+    pub results: Vec<PathBuf>,
+    /// A change is significant when its p-value is below this
+    #[arg(long, default_value = "0.05")]
+    pub significance: f64,
+    /// Significant changes smaller than this fraction of the baseline are not flagged
+    #[arg(long, default_value = "0.05")]
+    pub threshold: f64,
+}
+
+#[derive(clap::Args)]
+pub struct LocalArgs {
+    /// Address to serve the stand-in on
+    #[arg(long, default_value = "127.0.0.1:8000")]
+    pub listen: SocketAddr,
+    /// Tables to serve, like users:user or events:device:timestamp for a sort key. They start
+    /// empty
+    #[arg(long, value_delimiter = ',', default_value = "users:user")]
+    pub table: Vec<ddb_local::TableSchema>,
+    /// Latency to add to every request, in milliseconds
+    #[arg(long, default_value = "0")]
+    pub latency_ms: u64,
+    /// Up to this much more latency, uniformly, in milliseconds
+    #[arg(long, default_value = "0")]
+    pub latency_jitter_ms: u64,
+    /// Fraction of requests that fail with an InternalServerError
+    #[arg(long, default_value = "0")]
+    pub error_rate: f64,
+    /// Fraction of requests that fail with a ProvisionedThroughputExceededException
+    #[arg(long, default_value = "0")]
+    pub throttle_rate: f64,
+    /// Fraction of batch items to return unprocessed
+    #[arg(long, default_value = "0")]
+    pub unprocessed_rate: f64,
+    /// Check SigV4 signatures, and only accept requests signed with this access key id and
+    /// --secret-access-key
+    #[arg(long, requires = "secret_access_key")]
+    pub access_key_id: Option<String>,
+    #[arg(long, requires = "access_key_id")]
+    pub secret_access_key: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DriftMode {
+    /// Wrap around to the first keys, so keys go cold and come back a while later
+    Rotate,
+    /// Move on to keys that were never used before, so cold keys stay cold
+    Slide,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum LambdaEncoding {
+    /// Prefix every header with hahaha-, which lambda leaves alone
+    Renamed,
+    /// Pack every header and the uri into one base64 header, so none are merged or dropped
+    Envelope,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum RetryPolicy {
+    Standard,
+    Adaptive,
+    /// Never retry. Every failed attempt is reported as an error
+    Disabled,
 }
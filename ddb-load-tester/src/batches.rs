@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use aws_sdk_dynamodb::types::{
+    AttributeValue, KeysAndAttributes, PutRequest, ReturnConsumedCapacity, WriteRequest,
+};
+use rand::Rng;
+
+use crate::{
+    args::Args,
+    cache_status_interceptor::CacheStatus,
+    load_generator_task::user_item,
+    metrics::{Metrics, Operation},
+    ttl::Ttl,
+};
+
+/// DynamoDB's limits on items per batch
+const MAX_BATCH_GET_ITEMS: usize = 100;
+const MAX_BATCH_WRITE_ITEMS: usize = 25;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    pub get_items: usize,
+    pub write_items: usize,
+    /// Requests after the first one for unprocessed items
+    pub max_retries: u32,
+    /// The first retry waits up to this long, and each one after that up to twice as long
+    pub backoff: Duration,
+}
+impl BatchOptions {
+    pub fn new(args: &Args) -> Self {
+        assert!(
+            (1..=MAX_BATCH_GET_ITEMS).contains(&args.batch_get_items),
+            "--batch-get-items must be from 1 to {MAX_BATCH_GET_ITEMS}"
+        );
+        assert!(
+            (1..=MAX_BATCH_WRITE_ITEMS).contains(&args.batch_write_items),
+            "--batch-write-items must be from 1 to {MAX_BATCH_WRITE_ITEMS}"
+        );
+        Self {
+            get_items: args.batch_get_items,
+            write_items: args.batch_write_items,
+            max_retries: args.batch_max_retries,
+            backoff: Duration::from_millis(args.batch_backoff_ms),
+        }
+    }
+}
+
+/// BatchGetItem the keys, and request the `UnprocessedKeys` again until there are none left.
+pub async fn batch_get(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    keys: Vec<AttributeValue>,
+    options: BatchOptions,
+) {
+    let _in_flight = metrics.start_request();
+    let items = keys.len();
+    let keys = keys
+        .into_iter()
+        .map(|key| HashMap::from([("user".to_string(), key)]))
+        .collect();
+    let mut request_items = HashMap::from([(
+        "users".to_string(),
+        KeysAndAttributes::builder()
+            .set_keys(Some(keys))
+            .build()
+            .expect("must be complete keys and attributes"),
+    )]);
+    let mut retried_items = 0;
+    for attempt in 0.. {
+        let start = Instant::now();
+        let output = match client
+            .batch_get_item()
+            .set_request_items(Some(request_items))
+            .return_consumed_capacity(ReturnConsumedCapacity::Total)
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("failed to batch get items: {e:#?}");
+                metrics.record_error(Operation::BatchGetItem, &e);
+                return;
+            }
+        };
+        metrics.record_latency(
+            Operation::BatchGetItem,
+            start.elapsed(),
+            CacheStatus::Absent,
+        );
+        for capacity in output.consumed_capacity() {
+            metrics.record_read_capacity(Some(capacity));
+        }
+        request_items = output.unprocessed_keys.unwrap_or_default();
+        let unprocessed = request_items
+            .values()
+            .map(|keys| keys.keys().len())
+            .sum::<usize>();
+        if unprocessed == 0 || attempt == options.max_retries {
+            metrics.record_batch(Operation::BatchGetItem, items, retried_items, unprocessed);
+            return;
+        }
+        retried_items += unprocessed;
+        backoff(options.backoff, attempt).await;
+    }
+}
+
+/// BatchWriteItem puts of the keys, and write the `UnprocessedItems` again until there are none
+/// left.
+pub async fn batch_write(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    keys: Vec<AttributeValue>,
+    options: BatchOptions,
+    ttl: Option<Ttl>,
+) {
+    let _in_flight = metrics.start_request();
+    let items = keys.len();
+    let writes = keys
+        .into_iter()
+        .map(|key| {
+            WriteRequest::builder()
+                .put_request(
+                    PutRequest::builder()
+                        .set_item(Some(user_item(key, ttl.as_ref())))
+                        .build()
+                        .expect("must be a complete put request"),
+                )
+                .build()
+        })
+        .collect();
+    let mut request_items = HashMap::from([("users".to_string(), writes)]);
+    let mut retried_items = 0;
+    for attempt in 0.. {
+        let start = Instant::now();
+        let output = match client
+            .batch_write_item()
+            .set_request_items(Some(request_items))
+            .return_consumed_capacity(ReturnConsumedCapacity::Total)
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("failed to batch write items: {e:#?}");
+                metrics.record_error(Operation::BatchWriteItem, &e);
+                return;
+            }
+        };
+        metrics.record_latency(
+            Operation::BatchWriteItem,
+            start.elapsed(),
+            CacheStatus::Absent,
+        );
+        for capacity in output.consumed_capacity() {
+            metrics.record_write_capacity(Some(capacity));
+        }
+        request_items = output.unprocessed_items.unwrap_or_default();
+        let unprocessed = request_items.values().map(Vec::len).sum::<usize>();
+        if unprocessed == 0 || attempt == options.max_retries {
+            metrics.record_batch(Operation::BatchWriteItem, items, retried_items, unprocessed);
+            return;
+        }
+        retried_items += unprocessed;
+        backoff(options.backoff, attempt).await;
+    }
+}
+
+/// Exponential backoff with full jitter.
+async fn backoff(base: Duration, attempt: u32) {
+    let ceiling = base.saturating_mul(1 << attempt.min(16));
+    let delay = rand::rng().random_range(Duration::ZERO..=ceiling);
+    tokio::time::sleep(delay).await;
+}
@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+
+/// Where an accelerator says a response came from, via the `x-cache` response header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    /// The response was not marked, like when talking to dynamodb directly.
+    #[default]
+    Absent,
+}
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "hit",
+            CacheStatus::Miss => "miss",
+            CacheStatus::Absent => "absent",
+        }
+    }
+}
+
+/// Reads the `x-cache: HIT|MISS` header off of a response. Attach a fresh one to each request
+/// you want to attribute, and read the status after the request completes.
+#[derive(Debug, Clone, Default)]
+pub struct CacheStatusInterceptor {
+    status: Arc<Mutex<CacheStatus>>,
+}
+impl CacheStatusInterceptor {
+    pub fn status(&self) -> CacheStatus {
+        *self.status.lock().expect("local mutex works")
+    }
+}
+impl aws_sdk_dynamodb::config::Intercept for CacheStatusInterceptor {
+    fn name(&self) -> &'static str {
+        "CacheStatus"
+    }
+
+    fn read_before_deserialization(
+        &self,
+        context: &aws_sdk_dynamodb::config::interceptors::BeforeDeserializationInterceptorContextRef<
+            '_,
+        >,
+        _runtime_components: &aws_sdk_dynamodb::config::RuntimeComponents,
+        _cfg: &mut aws_sdk_dynamodb::config::ConfigBag,
+    ) -> Result<(), aws_sdk_dynamodb::error::BoxError> {
+        let status = match context.response().headers().get("x-cache") {
+            Some(value) if value.eq_ignore_ascii_case("hit") => CacheStatus::Hit,
+            Some(value) if value.eq_ignore_ascii_case("miss") => CacheStatus::Miss,
+            Some(value) => {
+                log::debug!("unrecognized x-cache header: {value}");
+                CacheStatus::Absent
+            }
+            None => CacheStatus::Absent,
+        };
+        *self.status.lock().expect("local mutex works") = status;
+
+        Ok(())
+    }
+}
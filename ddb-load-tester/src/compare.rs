@@ -0,0 +1,489 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    args::CompareArgs,
+    results::{self, IntervalRecord, OperationStats, Summary},
+};
+
+/// A run loaded back from its `--results-dir`.
+struct RunResults {
+    directory: PathBuf,
+    summary: Summary,
+    intervals: Vec<IntervalRecord>,
+}
+impl RunResults {
+    fn load(directory: &Path) -> Self {
+        let summary = serde_json::from_reader(
+            File::open(directory.join(results::SUMMARY_JSON))
+                .unwrap_or_else(|e| panic!("must be able to open {}: {e}", directory.display())),
+        )
+        .expect("must be able to parse the summary");
+        let intervals = BufReader::new(
+            File::open(directory.join(results::TIMESERIES_JSON))
+                .expect("must be able to open the time series"),
+        )
+        .lines()
+        .map(|line| {
+            serde_json::from_str(&line.expect("must be able to read the time series"))
+                .expect("must be able to parse the time series")
+        })
+        .collect();
+        Self {
+            directory: directory.to_path_buf(),
+            summary,
+            intervals,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Regression,
+    Improvement,
+    Unchanged,
+}
+
+struct Comparison {
+    name: String,
+    baseline: f64,
+    candidate: f64,
+    p_value: Option<f64>,
+    verdict: Verdict,
+}
+
+/// Compare each run against the first one. Returns true if any run regressed.
+pub fn compare(args: &CompareArgs) -> bool {
+    let runs: Vec<RunResults> = args.results.iter().map(|d| RunResults::load(d)).collect();
+    let (baseline, candidates) = runs.split_first().expect("clap requires 2 or more runs");
+
+    let mut regressed = false;
+    for candidate in candidates {
+        println!(
+            "baseline:  {} ({}, {:.0}s, {} intervals)",
+            baseline.directory.display(),
+            baseline.summary.target,
+            baseline.summary.duration_seconds,
+            baseline.intervals.len(),
+        );
+        println!(
+            "candidate: {} ({}, {:.0}s, {} intervals)",
+            candidate.directory.display(),
+            candidate.summary.target,
+            candidate.summary.duration_seconds,
+            candidate.intervals.len(),
+        );
+        println!(
+            "{:<28} {:>12} {:>12} {:>9} {:>9}",
+            "metric", "baseline", "candidate", "change", "p-value"
+        );
+        for comparison in comparisons(baseline, candidate, args) {
+            let change = relative_change(comparison.baseline, comparison.candidate);
+            println!(
+                "{:<28} {:>12.3} {:>12.3} {:>8.1}% {:>9} {}",
+                comparison.name,
+                comparison.baseline,
+                comparison.candidate,
+                change * 100.0,
+                comparison
+                    .p_value
+                    .map(|p| format!("{p:.4}"))
+                    .unwrap_or_else(|| "-".to_string()),
+                match comparison.verdict {
+                    Verdict::Regression => "REGRESSION",
+                    Verdict::Improvement => "improvement",
+                    Verdict::Unchanged => "",
+                },
+            );
+            regressed |= comparison.verdict == Verdict::Regression;
+        }
+        println!();
+    }
+    regressed
+}
+
+fn comparisons(
+    baseline: &RunResults,
+    candidate: &RunResults,
+    args: &CompareArgs,
+) -> Vec<Comparison> {
+    let mut comparisons = Vec::new();
+    let mut compare_samples = |name: String,
+                               baseline_value: f64,
+                               candidate_value: f64,
+                               baseline_samples: Vec<f64>,
+                               candidate_samples: Vec<f64>,
+                               lower_is_better: bool| {
+        let p_value = welch_t_test(&baseline_samples, &candidate_samples);
+        comparisons.push(Comparison {
+            verdict: verdict(
+                baseline_value,
+                candidate_value,
+                p_value,
+                lower_is_better,
+                args,
+            ),
+            name,
+            baseline: baseline_value,
+            candidate: candidate_value,
+            p_value,
+        });
+    };
+
+    compare_samples(
+        "achieved_tps".to_string(),
+        baseline.summary.achieved_tps,
+        candidate.summary.achieved_tps,
+        baseline.intervals.iter().map(|i| i.achieved_tps).collect(),
+        candidate.intervals.iter().map(|i| i.achieved_tps).collect(),
+        false,
+    );
+
+    type Statistic = (&'static str, fn(&OperationStats) -> f64);
+    let statistics: [Statistic; 5] = [
+        ("mean_ms", |s| s.mean_ms),
+        ("p50_ms", |s| s.p50_ms),
+        ("p90_ms", |s| s.p90_ms),
+        ("p99_ms", |s| s.p99_ms),
+        ("p999_ms", |s| s.p999_ms),
+    ];
+    for (operation, baseline_stats) in &baseline.summary.operations {
+        let Some(candidate_stats) = candidate.summary.operations.get(operation) else {
+            continue;
+        };
+        for (statistic, get) in statistics {
+            let samples = |run: &RunResults| -> Vec<f64> {
+                run.intervals
+                    .iter()
+                    .filter_map(|interval| interval.operations.get(operation))
+                    .filter(|stats| 0 < stats.requests)
+                    .map(get)
+                    .collect()
+            };
+            compare_samples(
+                format!("{operation} {statistic}"),
+                get(baseline_stats),
+                get(candidate_stats),
+                samples(baseline),
+                samples(candidate),
+                true,
+            );
+        }
+    }
+
+    let (baseline_errors, baseline_total) = error_counts(&baseline.summary.overall);
+    let (candidate_errors, candidate_total) = error_counts(&candidate.summary.overall);
+    let baseline_rate = rate(baseline_errors, baseline_total);
+    let candidate_rate = rate(candidate_errors, candidate_total);
+    let p_value = two_proportion_z_test(
+        baseline_errors,
+        baseline_total,
+        candidate_errors,
+        candidate_total,
+    );
+    comparisons.push(Comparison {
+        name: "error_rate".to_string(),
+        baseline: baseline_rate,
+        candidate: candidate_rate,
+        p_value,
+        verdict: verdict(baseline_rate, candidate_rate, p_value, true, args),
+    });
+
+    comparisons
+}
+
+fn verdict(
+    baseline: f64,
+    candidate: f64,
+    p_value: Option<f64>,
+    lower_is_better: bool,
+    args: &CompareArgs,
+) -> Verdict {
+    let significant = p_value.is_some_and(|p| p < args.significance);
+    let change = relative_change(baseline, candidate);
+    if !significant || change.abs() < args.threshold {
+        return Verdict::Unchanged;
+    }
+    if (0.0 < change) == lower_is_better {
+        Verdict::Regression
+    } else {
+        Verdict::Improvement
+    }
+}
+
+fn relative_change(baseline: f64, candidate: f64) -> f64 {
+    if baseline == 0.0 {
+        if candidate == 0.0 { 0.0 } else { f64::INFINITY }
+    } else {
+        (candidate - baseline) / baseline
+    }
+}
+
+fn error_counts(stats: &OperationStats) -> (u64, u64) {
+    (stats.errors, stats.requests + stats.errors)
+}
+
+fn rate(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+/// Two-sided p-value for a difference in means between samples with unequal variances.
+fn welch_t_test(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let Some((t, degrees_of_freedom)) = welch_t(a, b) else {
+        // Neither sample varies, so the means either differ for sure or not at all
+        return Some(if mean_and_variance(a).0 == mean_and_variance(b).0 {
+            1.0
+        } else {
+            0.0
+        });
+    };
+    Some(regularized_incomplete_beta(
+        degrees_of_freedom / (degrees_of_freedom + t * t),
+        degrees_of_freedom / 2.0,
+        0.5,
+    ))
+}
+
+/// Welch's t statistic and its degrees of freedom, for samples of at least 2 that vary.
+fn welch_t(a: &[f64], b: &[f64]) -> Option<(f64, f64)> {
+    let (mean_a, variance_a) = mean_and_variance(a);
+    let (mean_b, variance_b) = mean_and_variance(b);
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let standard_error = (variance_a / n_a + variance_b / n_b).sqrt();
+    if standard_error == 0.0 {
+        return None;
+    }
+    let t = (mean_a - mean_b) / standard_error;
+    let degrees_of_freedom = (variance_a / n_a + variance_b / n_b).powi(2)
+        / ((variance_a / n_a).powi(2) / (n_a - 1.0) + (variance_b / n_b).powi(2) / (n_b - 1.0));
+    Some((t, degrees_of_freedom))
+}
+
+/// Two-sided p-value for a difference between two proportions, like error rates.
+fn two_proportion_z_test(
+    successes_a: u64,
+    total_a: u64,
+    successes_b: u64,
+    total_b: u64,
+) -> Option<f64> {
+    if total_a == 0 || total_b == 0 {
+        return None;
+    }
+    let (n_a, n_b) = (total_a as f64, total_b as f64);
+    let pooled = (successes_a + successes_b) as f64 / (n_a + n_b);
+    let standard_error = (pooled * (1.0 - pooled) * (1.0 / n_a + 1.0 / n_b)).sqrt();
+    if standard_error == 0.0 {
+        return Some(1.0);
+    }
+    let z = (successes_a as f64 / n_a - successes_b as f64 / n_b) / standard_error;
+    Some(erfc(z.abs() / std::f64::consts::SQRT_2))
+}
+
+fn mean_and_variance(samples: &[f64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, variance)
+}
+
+/// Complementary error function for x >= 0, Abramowitz and Stegun 7.1.26. Accurate to 1.5e-7.
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    polynomial * (-x * x).exp()
+}
+
+/// I_x(a, b), by the continued fraction in Numerical Recipes.
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if 1.0 <= x {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut fraction = d;
+    for m in 1..200 {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 + even * d;
+        d = if d.abs() < TINY { 1.0 / TINY } else { 1.0 / d };
+        c = 1.0 + even / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        fraction *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 + odd * d;
+        d = if d.abs() < TINY { 1.0 / TINY } else { 1.0 / d };
+        c = 1.0 + odd / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    fraction
+}
+
+/// Lanczos approximation of ln(Γ(x)) for x > 0.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let mut y = x;
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for coefficient in COEFFICIENTS {
+        y += 1.0;
+        series += coefficient / y;
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn erfc_matches_reference_values() {
+        assert_close(erfc(0.0), 1.0, 1.5e-7);
+        assert_close(erfc(0.5), 0.4795001221869535, 1.5e-7);
+        assert_close(erfc(1.0), 0.15729920705028513, 1.5e-7);
+        assert_close(erfc(2.0), 0.004677734981047265, 1.5e-7);
+    }
+
+    #[test]
+    fn ln_gamma_matches_reference_values() {
+        assert_close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-10);
+        assert_close(ln_gamma(1.0), 0.0, 1e-10);
+        assert_close(ln_gamma(2.0), 0.0, 1e-10);
+        assert_close(ln_gamma(5.0), 24f64.ln(), 1e-10);
+        assert_close(ln_gamma(10.5), 13.940625219403763, 1e-9);
+    }
+
+    #[test]
+    fn incomplete_beta_matches_closed_forms() {
+        for x in [0.1, 0.25, 0.5, 0.9] {
+            // I_x(1, 1) = x, and I_x(a, 1) = x^a
+            assert_close(regularized_incomplete_beta(x, 1.0, 1.0), x, 1e-10);
+            assert_close(regularized_incomplete_beta(x, 3.0, 1.0), x.powi(3), 1e-10);
+            // I_x(a, b) = 1 - I_(1-x)(b, a)
+            assert_close(
+                regularized_incomplete_beta(x, 2.5, 0.5),
+                1.0 - regularized_incomplete_beta(1.0 - x, 0.5, 2.5),
+                1e-10,
+            );
+        }
+        assert_close(regularized_incomplete_beta(0.5, 4.0, 4.0), 0.5, 1e-10);
+        assert_eq!(regularized_incomplete_beta(0.0, 2.0, 3.0), 0.0);
+        assert_eq!(regularized_incomplete_beta(1.0, 2.0, 3.0), 1.0);
+        // The two-sided p of Student's t = 2.228 with 10 degrees of freedom is 0.05
+        assert_close(
+            regularized_incomplete_beta(10.0 / (10.0 + 2.228 * 2.228), 5.0, 0.5),
+            0.05,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn welch_t_test_matches_a_textbook_example() {
+        // Welch's t-test example 1 from Wikipedia
+        let a = [
+            27.5, 21.0, 19.0, 23.6, 17.0, 17.9, 16.9, 20.1, 21.9, 22.6, 23.1, 19.6, 19.0, 21.7,
+            21.4,
+        ];
+        let b = [
+            27.1, 22.0, 20.8, 23.4, 23.4, 23.5, 25.8, 22.0, 24.8, 20.2, 21.9, 22.1, 22.9, 20.5,
+            24.4,
+        ];
+        let (t, degrees_of_freedom) = welch_t(&a, &b).expect("the samples vary");
+        assert_close(t, -2.455356398286006, 1e-9);
+        assert_close(degrees_of_freedom, 24.98852929023142, 1e-9);
+        let p = welch_t_test(&a, &b).expect("enough samples");
+        assert_close(p, 0.021378, 1e-5);
+        assert_close(welch_t_test(&b, &a).expect("enough samples"), p, 1e-12);
+    }
+
+    #[test]
+    fn welch_t_test_edge_cases() {
+        assert_eq!(welch_t_test(&[1.0], &[1.0, 2.0]), None);
+        assert_eq!(welch_t_test(&[1.0, 2.0], &[]), None);
+        // Zero variance: the means are either the same or certainly different
+        assert_eq!(welch_t_test(&[3.0, 3.0], &[3.0, 3.0, 3.0]), Some(1.0));
+        assert_eq!(welch_t_test(&[3.0, 3.0], &[4.0, 4.0]), Some(0.0));
+        // Identical runs never differ
+        let run = [10.0, 12.0, 11.0, 13.0];
+        assert_close(
+            welch_t_test(&run, &run).expect("enough samples"),
+            1.0,
+            1e-12,
+        );
+        // Only one sample varying still gives a p-value
+        let p = welch_t_test(&[5.0, 5.0, 5.0], &[4.0, 6.0, 5.5]).expect("enough samples");
+        assert!((0.0..=1.0).contains(&p));
+    }
+
+    #[test]
+    fn two_proportion_z_test_matches_reference_values() {
+        // 3% against 5% of 1000 requests: z = -2.2822
+        assert_close(
+            two_proportion_z_test(30, 1000, 50, 1000).expect("requests"),
+            0.02247887336612527,
+            1e-6,
+        );
+        assert_close(
+            two_proportion_z_test(50, 1000, 50, 1000).expect("requests"),
+            1.0,
+            1e-7,
+        );
+        assert_eq!(two_proportion_z_test(0, 0, 5, 100), None);
+        // No errors, or only errors, on both sides is no difference
+        assert_eq!(two_proportion_z_test(0, 100, 0, 200), Some(1.0));
+        assert_eq!(two_proportion_z_test(100, 100, 200, 200), Some(1.0));
+    }
+}
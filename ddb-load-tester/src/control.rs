@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use crate::{metrics::Metrics, mix::OperationMix};
+
+/// Settings that can change while a load test is running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunSettings {
+    /// Request rate limit per second, across all workers
+    pub tps: u32,
+    /// Paused workers don't start any new requests
+    pub paused: bool,
+    /// Stopped workers don't start any new requests, and return once their requests finish
+    pub stopped: bool,
+    pub mix: OperationMix,
+}
+
+/// Shared handle to the live run settings. Workers subscribe to changes.
+///
+/// Every change is recorded as an annotation in the metrics.
+#[derive(Clone)]
+pub struct RunControl {
+    settings: Arc<watch::Sender<RunSettings>>,
+    metrics: Metrics,
+}
+impl RunControl {
+    pub fn new(settings: RunSettings, metrics: Metrics) -> Self {
+        Self {
+            settings: Arc::new(watch::Sender::new(settings)),
+            metrics,
+        }
+    }
+
+    pub fn settings(&self) -> RunSettings {
+        self.settings.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<RunSettings> {
+        self.settings.subscribe()
+    }
+
+    pub fn set_tps(&self, tps: u32) {
+        self.metrics.annotate("tps", format!("target tps {tps}"));
+        self.settings.send_modify(|settings| settings.tps = tps);
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        if paused {
+            self.metrics.annotate("pause", "paused".to_string());
+        } else {
+            self.metrics.annotate("resume", "resumed".to_string());
+        }
+        self.settings
+            .send_modify(|settings| settings.paused = paused);
+    }
+
+    /// End the run. Workers return once the requests they started finish.
+    pub fn stop(&self) {
+        self.settings
+            .send_modify(|settings| settings.stopped = true);
+    }
+
+    pub fn set_mix(&self, mix: OperationMix) {
+        self.metrics.annotate("mix", format!("operation mix {mix}"));
+        self.settings.send_modify(|settings| settings.mix = mix);
+    }
+}
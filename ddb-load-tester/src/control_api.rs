@@ -0,0 +1,260 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+};
+
+use crate::{control::RunControl, metrics::Metrics, mix::OperationMix, results::Summary};
+
+/// Builds a summary of the run so far, for `/dump`.
+pub type Summarize = Arc<dyn Fn() -> Summary + Send + Sync>;
+
+#[derive(Clone)]
+struct ControlState {
+    control: RunControl,
+    metrics: Metrics,
+    started: Instant,
+    summarize: Summarize,
+}
+
+#[derive(serde::Serialize)]
+struct Status {
+    tps: u32,
+    paused: bool,
+    mix: String,
+    elapsed_seconds: f64,
+}
+
+#[derive(serde::Deserialize)]
+struct SetTps {
+    tps: u32,
+}
+
+#[derive(serde::Deserialize)]
+struct SetMix {
+    mix: String,
+}
+
+/// Serve an http api to steer the run while it is going:
+///
+/// * `GET /status`
+/// * `POST /tps` with `{"tps": 100}`
+/// * `POST /mix` with `{"mix": "get=90,put=10"}`
+/// * `POST /pause` and `POST /resume`
+/// * `POST /dump` logs a summary of the run so far and returns it
+///
+/// Changes reply with the new status.
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    control: RunControl,
+    metrics: Metrics,
+    started: Instant,
+    summarize: Summarize,
+) {
+    log::info!(
+        "serving the control api on http://{}",
+        listener.local_addr().expect("must have an address")
+    );
+    axum::serve(listener, router(control, metrics, started, summarize))
+        .await
+        .expect("control endpoint must not fail");
+}
+
+fn router(control: RunControl, metrics: Metrics, started: Instant, summarize: Summarize) -> Router {
+    Router::new()
+        .route("/status", get(status))
+        .route("/tps", post(set_tps))
+        .route("/mix", post(set_mix))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/dump", post(dump))
+        .with_state(ControlState {
+            control,
+            metrics,
+            started,
+            summarize,
+        })
+}
+
+async fn status(State(state): State<ControlState>) -> Json<Status> {
+    Json(current_status(&state))
+}
+
+async fn set_tps(
+    State(state): State<ControlState>,
+    Json(request): Json<SetTps>,
+) -> Result<Json<Status>, (StatusCode, String)> {
+    if request.tps == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "tps must be above 0, use /pause to stop sending".to_string(),
+        ));
+    }
+    state.control.set_tps(request.tps);
+    Ok(Json(current_status(&state)))
+}
+
+async fn set_mix(
+    State(state): State<ControlState>,
+    Json(request): Json<SetMix>,
+) -> Result<Json<Status>, (StatusCode, String)> {
+    let mix: OperationMix = request
+        .mix
+        .parse()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    state.control.set_mix(mix);
+    Ok(Json(current_status(&state)))
+}
+
+async fn pause(State(state): State<ControlState>) -> Json<Status> {
+    state.control.set_paused(true);
+    Json(current_status(&state))
+}
+
+async fn resume(State(state): State<ControlState>) -> Json<Status> {
+    state.control.set_paused(false);
+    Json(current_status(&state))
+}
+
+async fn dump(State(state): State<ControlState>) -> Json<Summary> {
+    let summary = (state.summarize)();
+    state.metrics.annotate("dump", "stats dump".to_string());
+    log::info!(
+        "stats dump: {}",
+        serde_json::to_string(&summary).expect("summaries serialize")
+    );
+    Json(summary)
+}
+
+fn current_status(state: &ControlState) -> Status {
+    let settings = state.control.settings();
+    Status {
+        tps: settings.tps,
+        paused: settings.paused,
+        mix: settings.mix.to_string(),
+        elapsed_seconds: state.started.elapsed().as_secs_f64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Instant};
+
+    use clap::Parser;
+    use serde_json::{Value, json};
+
+    use super::serve;
+    use crate::{
+        args::Args,
+        control::{RunControl, RunSettings},
+        cost::OnDemandPricing,
+        metrics::Metrics,
+        results,
+    };
+
+    /// A control api for a run at 10 tps of gets, and a way to post to it.
+    async fn start() -> (RunControl, impl AsyncFn(&str, Value) -> (u16, String)) {
+        let args = Args::parse_from(["ddb-load-tester"]);
+        let metrics = Metrics::configure(&args, None);
+        let control = RunControl::new(
+            RunSettings {
+                tps: 10,
+                paused: false,
+                stopped: false,
+                mix: "get".parse().expect("get is a mix"),
+            },
+            metrics.clone(),
+        );
+        let started = Instant::now();
+        let summarize = Arc::new({
+            let metrics = metrics.clone();
+            let control = control.clone();
+            let pricing = OnDemandPricing::new(&args);
+            move || results::summarize(&metrics, pricing, started, 0, control.settings().tps)
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("must bind a local port");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("must have an address")
+        );
+        tokio::spawn(serve(
+            listener,
+            control.clone(),
+            metrics,
+            started,
+            summarize,
+        ));
+        let post = async move |path: &str, body: Value| {
+            let response = reqwest::Client::new()
+                .post(format!("{url}{path}"))
+                .json(&body)
+                .send()
+                .await
+                .expect("the control api must answer");
+            let status = response.status().as_u16();
+            (status, response.text().await.expect("responses are text"))
+        };
+        (control, post)
+    }
+
+    fn status(body: &str) -> Value {
+        serde_json::from_str(body).expect("changes reply with the status")
+    }
+
+    #[tokio::test]
+    async fn tps_changes_the_target_rate() {
+        let (control, post) = start().await;
+        let (code, body) = post("/tps", json!({"tps": 250})).await;
+        assert_eq!(code, 200);
+        assert_eq!(status(&body)["tps"], 250);
+        assert_eq!(control.settings().tps, 250);
+
+        let (code, _) = post("/tps", json!({"tps": 0})).await;
+        assert_eq!(code, 400);
+        assert_eq!(control.settings().tps, 250);
+    }
+
+    #[tokio::test]
+    async fn mix_changes_the_operations() {
+        let (control, post) = start().await;
+        let (code, body) = post("/mix", json!({"mix": "get=90,put=10"})).await;
+        assert_eq!(code, 200);
+        assert_eq!(status(&body)["mix"], "get=90,put=10");
+        assert_eq!(control.settings().mix.to_string(), "get=90,put=10");
+
+        let (code, body) = post("/mix", json!({"mix": "get=90,fly=10"})).await;
+        assert_eq!(code, 400);
+        assert!(body.contains("unknown operation fly"), "{body}");
+        assert_eq!(control.settings().mix.to_string(), "get=90,put=10");
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume() {
+        let (control, post) = start().await;
+        let (code, body) = post("/pause", Value::Null).await;
+        assert_eq!(code, 200);
+        assert_eq!(status(&body)["paused"], true);
+        assert!(control.settings().paused);
+
+        let (code, body) = post("/resume", Value::Null).await;
+        assert_eq!(code, 200);
+        assert_eq!(status(&body)["paused"], false);
+        assert!(!control.settings().paused);
+    }
+
+    #[tokio::test]
+    async fn dump_returns_a_summary_at_the_current_rate() {
+        let (_control, post) = start().await;
+        post("/tps", json!({"tps": 40})).await;
+        let (code, body) = post("/dump", Value::Null).await;
+        assert_eq!(code, 200);
+        let summary: results::Summary =
+            serde_json::from_str(&body).expect("dump replies with a summary");
+        assert_eq!(summary.target_tps, 40);
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    args::Args,
+    cost::OnDemandPricing,
+    item_generator,
+    local_metrics::Snapshot,
+    results::{self, ResultsWriter},
+    worker::{self, Assignment, Start, WorkerReport},
+};
+
+/// Run the load test on `--coordinate` workers instead of in this process.
+///
+/// The target rate and the keyspace are split between the workers, they all start at the same
+/// time once every one of them has populated, and their metrics are merged into one report.
+pub async fn coordinate(args: &Args) {
+    let duration_seconds = args
+        .duration
+        .expect("clap requires --duration with --coordinate");
+    let workers = &args.coordinate;
+    assert!(
+        workers.len() as u64 <= args.items,
+        "must have at least one item per worker"
+    );
+    let http = reqwest::Client::new();
+
+    // Workers answer once they are ready, after populating, so assign them all at once
+    let mut assigning = tokio::task::JoinSet::new();
+    for (index, worker) in workers.iter().enumerate() {
+        let assignment = assignment(args, index, duration_seconds);
+        log::info!("assigning {worker}: {assignment:?}");
+        let request = http
+            .post(format!("http://{worker}/assign"))
+            .json(&assignment)
+            .send();
+        let worker = worker.clone();
+        assigning.spawn(async move {
+            request
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .unwrap_or_else(|e| panic!("must be able to assign worker {worker}: {e}"));
+        });
+    }
+    while let Some(assigned) = assigning.join_next().await {
+        assigned.expect("assigning must succeed");
+    }
+
+    let start_at_ms = results::unix_millis() + args.start_delay_ms;
+    for worker in workers {
+        http.post(format!("http://{worker}/start"))
+            .json(&Start { start_at_ms })
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .unwrap_or_else(|e| panic!("must be able to start worker {worker}: {e}"));
+    }
+    worker::wait_until(start_at_ms).await;
+    let started = Instant::now();
+    log::info!("started {} workers", workers.len());
+
+    let mut results = args.results_dir.as_deref().map(ResultsWriter::create);
+    let deadline = tokio::time::sleep(Duration::from_secs(duration_seconds));
+    tokio::pin!(deadline);
+    let mut results_interval = tokio::time::interval(Duration::from_secs(args.results_interval));
+    results_interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            _ = results_interval.tick(), if results.is_some() => {
+                match collect(&http, workers).await {
+                    Ok((_, snapshot)) => {
+                        if let Some(results) = &mut results {
+                            results.record_interval(snapshot, started, args.tps);
+                        }
+                    }
+                    Err(e) => log::warn!("skipping an interval: {e}"),
+                }
+            }
+        }
+    }
+
+    // Rates are over the run, not the time the workers take to drain afterwards
+    let run_seconds = started.elapsed().as_secs_f64();
+    log::info!("run duration elapsed, waiting for the workers to finish");
+    let snapshot = loop {
+        match collect(&http, workers).await {
+            Ok((true, snapshot)) => break snapshot,
+            Ok((false, _)) => (),
+            Err(e) => log::warn!("waiting for the workers: {e}"),
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    };
+    for worker in workers {
+        if let Err(e) = http.post(format!("http://{worker}/shutdown")).send().await {
+            log::warn!("failed to shut down worker {worker}: {e}");
+        }
+    }
+
+    let summary = results::summarize_snapshot(
+        &snapshot,
+        args.scenario.as_deref().unwrap_or("unset"),
+        OnDemandPricing::new(args),
+        run_seconds,
+        start_at_ms,
+        args.tps,
+    );
+    log::info!(
+        "{} workers: achieved {:.1} of {} tps, {} requests, {} errors, estimated on-demand cost ${:.6}",
+        workers.len(),
+        summary.achieved_tps,
+        summary.target_tps,
+        summary.overall.requests,
+        summary.overall.errors,
+        summary.estimated_cost_usd,
+    );
+    for (operation, stats) in &summary.operations {
+        log::info!(
+            "{operation}: {} requests, {} errors, p50 {:.3}ms, p99 {:.3}ms, p99.9 {:.3}ms",
+            stats.requests,
+            stats.errors,
+            stats.p50_ms,
+            stats.p99_ms,
+            stats.p999_ms,
+        );
+    }
+    if let Some(results) = results {
+        results.finish(&summary);
+    }
+}
+
+/// Worker `index`'s share. Worker `index` owns shard `index` of the keyspace, and leftover rate
+/// goes to the first workers.
+fn assignment(args: &Args, index: usize, duration_seconds: u64) -> Assignment {
+    let workers = args.coordinate.len() as u64;
+    let tps = item_generator::partition(0..args.tps as u64, workers, index as u64);
+    if tps.is_empty() {
+        log::warn!("worker {index} gets no load, --tps is lower than the number of workers");
+    }
+    Assignment {
+        worker: index,
+        tps: (tps.end - tps.start) as u32,
+        seed: args.seed,
+        items: args.items,
+        item_key_length: args.item_key_length,
+        shard_index: index as u64,
+        shard_count: workers,
+        shard_overlap: args.shard_overlap,
+        partition_workers: args.partition_workers,
+        populate: args.populate,
+        mix: args.mix.to_string(),
+        duration_seconds,
+    }
+}
+
+/// Every worker's report merged together, and whether all of them are done.
+async fn collect(http: &reqwest::Client, workers: &[String]) -> reqwest::Result<(bool, Snapshot)> {
+    let mut all_done = true;
+    let mut merged = Snapshot::default();
+    for worker in workers {
+        let report: WorkerReport = http
+            .get(format!("http://{worker}/report"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        all_done &= report.done;
+        merged.merge(&report.snapshot);
+    }
+    Ok((all_done, merged))
+}
@@ -0,0 +1,35 @@
+use crate::{args::Args, metrics::Metrics};
+
+/// DynamoDB on-demand request unit pricing, in USD per million request units.
+///
+/// With on-demand capacity, one consumed read capacity unit is billed as one read
+/// request unit, and likewise for writes, so consumed capacity maps directly to cost.
+#[derive(Debug, Clone, Copy)]
+pub struct OnDemandPricing {
+    read_per_million: f64,
+    write_per_million: f64,
+}
+impl OnDemandPricing {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            read_per_million: args.read_request_unit_price,
+            write_per_million: args.write_request_unit_price,
+        }
+    }
+
+    pub fn estimate(&self, read_units: f64, write_units: f64) -> f64 {
+        (read_units * self.read_per_million + write_units * self.write_per_million) / 1_000_000.0
+    }
+}
+
+/// Log the capacity consumed so far and what it would cost on an on-demand table.
+///
+/// Run the same workload with and without `--accelerator-url` to compare the spend side by side.
+pub fn log_cost_report(metrics: &Metrics, pricing: OnDemandPricing) {
+    let (read_units, write_units) = metrics.consumed_capacity();
+    log::info!(
+        "target {target}: consumed {read_units:.1} RCU and {write_units:.1} WCU, estimated on-demand cost ${cost:.6}",
+        target = metrics.target(),
+        cost = pricing.estimate(read_units, write_units),
+    );
+}
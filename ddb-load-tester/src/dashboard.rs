@@ -0,0 +1,278 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Sparkline, Table},
+};
+use tokio::sync::Notify;
+
+use crate::{
+    control::RunControl,
+    local_metrics::{self, Snapshot},
+};
+
+const REFRESH: Duration = Duration::from_secs(1);
+const HISTORY: usize = 120;
+
+/// A live terminal view of the run, refreshed every second from the local metrics.
+///
+/// Keys: space pauses and resumes, + and - change the rate by 10%, q quits.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    quit: Arc<Notify>,
+    task: tokio::task::JoinHandle<()>,
+}
+impl Dashboard {
+    pub fn start(control: RunControl, started: Instant) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let quit = Arc::new(Notify::new());
+        let task = tokio::task::spawn_blocking({
+            let stop = stop.clone();
+            let quit = quit.clone();
+            move || {
+                let mut terminal = ratatui::init();
+                let result = DashboardState::new(control, started).run(&mut terminal, &stop, &quit);
+                ratatui::restore();
+                if let Err(e) = result {
+                    log::error!("dashboard failed: {e}");
+                }
+            }
+        });
+        Self { stop, quit, task }
+    }
+
+    /// Completes when the user asks to quit from the dashboard.
+    pub async fn quit_requested(&self) {
+        self.quit.notified().await
+    }
+
+    /// Close the dashboard and give the terminal back.
+    pub async fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.task.await.expect("dashboard must not panic");
+    }
+}
+
+struct DashboardState {
+    control: RunControl,
+    started: Instant,
+    previous: Snapshot,
+    previous_at: Instant,
+    achieved_tps: f64,
+    in_flight: i64,
+    p50_history: VecDeque<u64>,
+    p99_history: VecDeque<u64>,
+    /// error code -> (whole run, last interval)
+    errors: BTreeMap<String, (u64, u64)>,
+    cache_hits: u64,
+    cache_misses: u64,
+}
+impl DashboardState {
+    fn new(control: RunControl, started: Instant) -> Self {
+        Self {
+            control,
+            started,
+            previous: local_metrics::default_registry().snapshot(),
+            previous_at: Instant::now(),
+            achieved_tps: 0.0,
+            in_flight: 0,
+            p50_history: VecDeque::with_capacity(HISTORY),
+            p99_history: VecDeque::with_capacity(HISTORY),
+            errors: BTreeMap::new(),
+            cache_hits: 0,
+            cache_misses: 0,
+        }
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        stop: &AtomicBool,
+        quit: &Notify,
+    ) -> std::io::Result<()> {
+        let mut next_refresh = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            if next_refresh <= Instant::now() {
+                self.refresh();
+                next_refresh += REFRESH;
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            // Poll briefly so the stop flag and refresh are checked regularly.
+            if event::poll(Duration::from_millis(100))?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                let settings = self.control.settings();
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => quit.notify_one(),
+                    // Raw mode swallows the signal, so handle ctrl-c here.
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        quit.notify_one()
+                    }
+                    KeyCode::Char(' ') | KeyCode::Char('p') => {
+                        self.control.set_paused(!settings.paused)
+                    }
+                    KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Up => self
+                        .control
+                        .set_tps((settings.tps + settings.tps / 10).max(settings.tps + 1)),
+                    KeyCode::Char('-') | KeyCode::Down => self.control.set_tps(
+                        (settings.tps - settings.tps / 10)
+                            .min(settings.tps.saturating_sub(1))
+                            .max(1),
+                    ),
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn refresh(&mut self) {
+        let now = Instant::now();
+        let snapshot = local_metrics::default_registry().snapshot();
+        let interval = snapshot.since(&self.previous);
+        let seconds = now.duration_since(self.previous_at).as_secs_f64();
+
+        let all = |_: &BTreeMap<String, String>| true;
+        let completed = interval.counter("requests", all) + interval.counter("errors", all);
+        self.achieved_tps = completed as f64 / seconds;
+        self.in_flight = snapshot.gauge("in_flight", all);
+        let latency = interval.histogram("latency", all);
+        push_bounded(&mut self.p50_history, latency.percentile(0.5) / 1000);
+        push_bounded(&mut self.p99_history, latency.percentile(0.99) / 1000);
+
+        self.errors = snapshot
+            .label_values("errors", "error")
+            .into_iter()
+            .map(|code| {
+                let matches =
+                    |labels: &BTreeMap<String, String>| labels.get("error") == Some(&code);
+                let counts = (
+                    snapshot.counter("errors", matches),
+                    interval.counter("errors", matches),
+                );
+                (code, counts)
+            })
+            .collect();
+        let cache = |status: &'static str| {
+            move |labels: &BTreeMap<String, String>| {
+                labels.get("cache").map(String::as_str) == Some(status)
+            }
+        };
+        self.cache_hits = snapshot.counter("requests", cache("hit"));
+        self.cache_misses = snapshot.counter("requests", cache("miss"));
+
+        self.previous = snapshot;
+        self.previous_at = now;
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, p50, p99, bottom, help] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Min(4),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [errors, cache] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(bottom);
+
+        let settings = self.control.settings();
+        let state = if settings.paused {
+            "PAUSED".red().bold()
+        } else {
+            "running".green()
+        };
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(vec![
+                    state,
+                    format!("  elapsed {:.0}s", self.started.elapsed().as_secs_f64()).into(),
+                ]),
+                Line::from(format!(
+                    "target {} tps   achieved {:.1} tps   in flight {}",
+                    settings.tps, self.achieved_tps, self.in_flight
+                )),
+            ])
+            .block(Block::bordered().title("ddb-load-tester")),
+            header,
+        );
+
+        for (area, name, history, color) in [
+            (p50, "p50", &self.p50_history, Color::Cyan),
+            (p99, "p99", &self.p99_history, Color::Yellow),
+        ] {
+            let latest = history.back().copied().unwrap_or_default();
+            let data: Vec<u64> = history.iter().copied().collect();
+            frame.render_widget(
+                Sparkline::default()
+                    .block(Block::bordered().title(format!(
+                        "{name} latency {:.2}ms (last {}s)",
+                        latest as f64 / 1000.0,
+                        history.len()
+                    )))
+                    .data(&data)
+                    .style(Style::default().fg(color)),
+                area,
+            );
+        }
+
+        frame.render_widget(
+            Table::new(
+                self.errors.iter().map(|(code, (total, last))| {
+                    Row::new(vec![code.clone(), total.to_string(), last.to_string()])
+                }),
+                [
+                    Constraint::Percentage(60),
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(20),
+                ],
+            )
+            .header(Row::new(vec!["error", "total", "last 1s"]).bold())
+            .block(Block::bordered().title("errors")),
+            errors,
+        );
+
+        let cached = self.cache_hits + self.cache_misses;
+        let hit_ratio = if cached == 0 {
+            "-".to_string()
+        } else {
+            format!("{:.1}%", 100.0 * self.cache_hits as f64 / cached as f64)
+        };
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!("hits   {}", self.cache_hits)),
+                Line::from(format!("misses {}", self.cache_misses)),
+                Line::from(format!("ratio  {hit_ratio}")),
+            ])
+            .block(Block::bordered().title("cache")),
+            cache,
+        );
+
+        frame.render_widget(
+            Line::from("space: pause/resume   +/-: rate ±10%   q: quit").dim(),
+            help,
+        );
+    }
+}
+
+fn push_bounded(history: &mut VecDeque<u64>, value: u64) {
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
@@ -0,0 +1,90 @@
+use std::{sync::Arc, time::Duration};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use tokio::{sync::Barrier, task::JoinSet};
+
+use crate::{
+    cache_status_interceptor::CacheStatus,
+    load_generator_task::{GetReply, get_user, put_item},
+    metrics::Metrics,
+};
+
+/// A thundering herd: every round, `size` concurrent GetItems for one key that isn't cached yet.
+///
+/// The accelerator caches GetItems until they expire and never invalidates them, so a key that
+/// was just written is the same as one whose cache entry expired or was invalidated. Each round
+/// writes a new key and then releases the whole herd at once. Without request coalescing, every
+/// request that misses the cache reaches DynamoDB.
+pub async fn herd_task(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    size: usize,
+    interval: Duration,
+    run_id: u64,
+) {
+    let mut rounds = tokio::time::interval(interval);
+    rounds.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    for round in 0.. {
+        rounds.tick().await;
+        let key = AttributeValue::S(format!("herd-{run_id}-{round}"));
+        put_item(&client, &metrics, key.clone(), None).await;
+
+        let barrier = Arc::new(Barrier::new(size));
+        let mut herd = JoinSet::new();
+        for _ in 0..size {
+            let client = client.clone();
+            let metrics = metrics.clone();
+            let barrier = barrier.clone();
+            let key = key.clone();
+            herd.spawn(async move {
+                let _in_flight = metrics.start_request();
+                barrier.wait().await;
+                get_user(&client, &metrics, key, false).await
+            });
+        }
+        let mut replies = Vec::with_capacity(size);
+        while let Some(reply) = herd.join_next().await {
+            replies.push(reply.expect("herd requests must not panic"));
+        }
+        report_round(&metrics, round, &replies);
+    }
+}
+
+fn report_round(metrics: &Metrics, round: u64, replies: &[Option<GetReply>]) {
+    let completed: Vec<&GetReply> = replies.iter().flatten().collect();
+    let errors = replies.len() - completed.len();
+    let hits = completed
+        .iter()
+        .filter(|reply| reply.cache_status == CacheStatus::Hit)
+        .count();
+    // Without an accelerator every request reaches DynamoDB, and there is no x-cache header.
+    let reached_dynamodb = completed.len() - hits;
+    let read_units = completed
+        .iter()
+        .filter(|reply| reply.cache_status != CacheStatus::Hit)
+        .filter_map(|reply| reply.output.consumed_capacity())
+        .filter_map(|capacity| capacity.capacity_units())
+        .fold(0.0, |total, units| total + units);
+    let mut latencies: Vec<Duration> = completed.iter().map(|reply| reply.latency).collect();
+    latencies.sort();
+    let percentile = |quantile: f64| {
+        latencies
+            .get(((latencies.len() as f64 * quantile).ceil() as usize).saturating_sub(1))
+            .copied()
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0
+    };
+
+    metrics.record_herd_round(replies.len() as u64, reached_dynamodb as u64);
+    metrics.annotate(
+        "herd",
+        format!(
+            "round {round}: {} requests, {reached_dynamodb} reached dynamodb ({read_units:.1} RCU), {hits} cache hits, {errors} errors, latency p50 {:.3}ms p99 {:.3}ms max {:.3}ms",
+            replies.len(),
+            percentile(0.5),
+            percentile(0.99),
+            percentile(1.0),
+        ),
+    );
+}
@@ -1,13 +1,33 @@
+use std::{
+    collections::HashSet,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use rand::{
     Rng, SeedableRng,
     distr::{Alphabetic, SampleString},
 };
 
+/// Picks random keys from a keyspace that is the same for every process with the same seed.
+///
+/// A generator can be narrowed to a shard of the keyspace. It owns the keys of its shard, and may
+/// also read some of the keys after them, wrapping around within the keyspace it was sharded from.
 #[derive(Clone)]
 pub struct ItemGenerator {
-    items: Vec<String>,
+    items: Arc<[String]>,
+    /// Keys wrap around within these items
+    bounds: Range<usize>,
+    /// Keys are picked from `window_len` items starting here
+    window_start: usize,
+    window_len: usize,
+    /// The first `owned` items of the window belong to this generator alone
+    owned: usize,
+    drift: Option<Drift>,
     random: rand::rngs::SmallRng,
 }
+
 impl ItemGenerator {
     pub fn new(seed: u64, item_count: u64, length: usize) -> Self {
         let mut random = rand::rngs::SmallRng::seed_from_u64(seed);
@@ -15,11 +35,237 @@ impl ItemGenerator {
         for _ in 0..item_count {
             items.push(Alphabetic.sample_string(&mut random, length));
         }
-        Self { items, random }
+        Self {
+            bounds: 0..items.len(),
+            window_start: 0,
+            window_len: items.len(),
+            owned: items.len(),
+            drift: None,
+            items: items.into(),
+            random,
+        }
+    }
+
+    /// Narrow this generator to shard `index` of `count` disjoint shards of its keys.
+    /// `overlap` extends the shard by that fraction of its size into the next shards.
+    pub fn shard(self, index: u64, count: u64, overlap: f64) -> Self {
+        assert_eq!(
+            self.window_len, self.owned,
+            "only a shard without overlap can be sharded again"
+        );
+        let bounds = self.window_start..self.window_start + self.owned;
+        let owned = partition(bounds.start as u64..bounds.end as u64, count, index);
+        assert!(!owned.is_empty(), "a shard must have at least one item");
+        let owned = owned.start as usize..owned.end as usize;
+        let window_len =
+            (owned.len() + (owned.len() as f64 * overlap).ceil() as usize).min(bounds.len());
+        Self {
+            bounds,
+            window_start: owned.start,
+            window_len,
+            owned: owned.len(),
+            ..self
+        }
+    }
+
+    /// Only pick keys from the first `fraction` of the window, and move that working set through the
+    /// keys over time with `drift`. Keys ahead of the working set are cold until it reaches them.
+    pub fn working_set(self, fraction: f64, drift: Option<Drift>) -> Self {
+        let window_len =
+            ((self.window_len as f64 * fraction).ceil() as usize).clamp(1, self.window_len);
+        Self {
+            window_len,
+            drift,
+            ..self
+        }
+    }
+
+    /// Pick keys with a random sequence of its own, so copies of a generator with different
+    /// `stream`s don't pick the same keys in lockstep. The same seed and stream pick the same keys.
+    pub fn stream(self, stream: u64) -> Self {
+        let seed = self.random.clone().random::<u64>() ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        Self {
+            random: rand::rngs::SmallRng::seed_from_u64(seed),
+            ..self
+        }
+    }
+
+    /// The keys that belong to this generator alone, without the overlap.
+    pub fn owned(&self) -> &[String] {
+        &self.items[self.window_start..self.window_start + self.owned]
+    }
+
+    /// Up to `count` distinct keys, for requests that can't name an item twice.
+    /// There may be fewer if there are few keys to pick from.
+    pub fn next_distinct(&mut self, count: usize) -> Vec<String> {
+        let mut keys = HashSet::with_capacity(count);
+        for _ in 0..count * 10 {
+            keys.insert(self.next());
+            if keys.len() == count {
+                break;
+            }
+        }
+        keys.into_iter().collect()
     }
 
     pub fn next(&mut self) -> String {
-        let index = self.random.random_range(0..self.items.len());
-        self.items[index].clone()
+        let drifted = self.drift.map_or(0, |drift| {
+            (drift.started.elapsed().as_secs_f64() / drift.period.as_secs_f64()
+                * self.window_len as f64) as usize
+        });
+        let offset = self.window_start - self.bounds.start
+            + drifted
+            + self.random.random_range(0..self.window_len);
+        let key = &self.items[self.bounds.start + offset % self.bounds.len()];
+        // Each lap of a sliding window gets its own keys
+        let lap = offset / self.bounds.len();
+        match self.drift {
+            Some(Drift { slide: true, .. }) if 0 < lap => format!("{key}-{lap}"),
+            _ => key.clone(),
+        }
+    }
+}
+
+/// Moves a generator's window through its keys over time.
+#[derive(Debug, Clone, Copy)]
+pub struct Drift {
+    pub started: Instant,
+    /// How long the window takes to move by its own size
+    pub period: Duration,
+    /// Past the last key, use new keys instead of wrapping back to the first ones
+    pub slide: bool,
+}
+
+/// Part `index` of `count` nearly equal, contiguous parts of `range`.
+/// The first parts get the leftovers.
+pub fn partition(range: Range<u64>, count: u64, index: u64) -> Range<u64> {
+    let total = range.end - range.start;
+    let part = |index: u64| total / count + u64::from(index < total % count);
+    let start = range.start + (0..index).map(part).sum::<u64>();
+    start..start + part(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{Drift, ItemGenerator, partition};
+
+    /// The keys `next` returns from many draws.
+    fn drawn(generator: &mut ItemGenerator) -> HashSet<String> {
+        (0..5000).map(|_| generator.next()).collect()
+    }
+
+    #[test]
+    fn partitions_cover_the_range_once() {
+        for (range, count) in [(0..10, 3), (5..105, 7), (0..3, 5), (7..8, 1), (0..100, 100)] {
+            let parts: Vec<_> = (0..count)
+                .map(|index| partition(range.clone(), count, index))
+                .collect();
+            assert_eq!(parts[0].start, range.start);
+            assert_eq!(parts[count as usize - 1].end, range.end);
+            for pair in parts.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start, "parts are contiguous");
+                assert!(pair[1].end - pair[1].start <= pair[0].end - pair[0].start);
+            }
+            let sizes: Vec<_> = parts.iter().map(|part| part.end - part.start).collect();
+            assert!(sizes.iter().max().unwrap_or(&0) - sizes.iter().min().unwrap_or(&0) <= 1);
+        }
+    }
+
+    #[test]
+    fn shards_own_disjoint_keys_that_cover_the_keyspace() {
+        let all = ItemGenerator::new(1, 101, 12);
+        let mut owned = Vec::new();
+        for index in 0..4 {
+            // Process shards, each split again between workers like --partition-workers
+            let process = all.clone().shard(index, 4, 0.0);
+            for worker in 0..3 {
+                owned.extend(process.clone().shard(worker, 3, 0.0).owned().to_vec());
+            }
+        }
+        assert_eq!(owned, all.owned());
+    }
+
+    #[test]
+    fn shards_draw_only_their_own_keys_without_overlap() {
+        let all = ItemGenerator::new(2, 40, 12);
+        let mut seen = HashSet::new();
+        for index in 0..4 {
+            let mut shard = all.clone().shard(index, 4, 0.0);
+            let owned: HashSet<_> = shard.owned().iter().cloned().collect();
+            let keys = drawn(&mut shard);
+            assert_eq!(keys, owned);
+            assert!(seen.is_disjoint(&keys), "shard {index} shares keys");
+            seen.extend(keys);
+        }
+        assert_eq!(seen.len(), 40);
+    }
+
+    #[test]
+    fn overlap_reaches_into_the_next_shards_and_wraps_around() {
+        let all = ItemGenerator::new(3, 40, 12);
+        let shards: Vec<_> = (0..4)
+            .map(|index| all.clone().shard(index, 4, 0.5))
+            .collect();
+        let owned = |index: usize| -> HashSet<String> {
+            shards[index % 4].owned().iter().cloned().collect()
+        };
+        for (index, shard) in shards.iter().enumerate() {
+            let keys = drawn(&mut shard.clone());
+            // Half of the next shard's 10 keys, and nothing else
+            assert!(owned(index).is_subset(&keys));
+            let borrowed: HashSet<_> = keys.difference(&owned(index)).cloned().collect();
+            assert_eq!(borrowed.len(), 5);
+            assert!(
+                borrowed.is_subset(&owned(index + 1)),
+                "shard {index} wraps to the next"
+            );
+        }
+
+        // Overlap past the whole keyspace is the whole keyspace
+        let keys = drawn(&mut all.clone().shard(1, 4, 10.0));
+        assert_eq!(keys.len(), 40);
+    }
+
+    #[test]
+    fn streams_pick_their_own_keys() {
+        let all = ItemGenerator::new(6, 1000, 12);
+        let picks = |stream: u64| -> Vec<String> {
+            let mut generator = all.clone().stream(stream);
+            (0..20).map(|_| generator.next()).collect()
+        };
+        assert_eq!(picks(0), picks(0), "a stream is repeatable");
+        assert_ne!(picks(0), picks(1), "streams don't pick in lockstep");
+        assert_eq!(all.clone().stream(1).owned(), all.owned());
+    }
+
+    #[test]
+    fn working_sets_are_the_start_of_the_window() {
+        let all = ItemGenerator::new(4, 40, 12);
+        let shard = all.clone().shard(3, 4, 0.0);
+        let owned = shard.owned().to_vec();
+        let keys = drawn(&mut shard.working_set(0.3, None));
+        assert_eq!(keys, owned[..3].iter().cloned().collect());
+
+        // The working set never shrinks to nothing
+        let keys = drawn(&mut all.clone().working_set(0.0001, None));
+        assert_eq!(keys.len(), 1);
+    }
+
+    #[test]
+    fn sliding_working_sets_use_new_keys_after_a_lap() {
+        let drift = Drift {
+            started: std::time::Instant::now() - std::time::Duration::from_secs(100),
+            period: std::time::Duration::from_secs(1),
+            slide: true,
+        };
+        let mut generator = ItemGenerator::new(5, 10, 12).working_set(0.5, Some(drift));
+        let key = generator.next();
+        let (base, lap) = key
+            .rsplit_once('-')
+            .expect("keys after a lap have a suffix");
+        assert!(generator.owned().iter().any(|owned| owned == base));
+        assert!(0 < lap.parse::<usize>().expect("laps are numbered"));
     }
 }
@@ -1,53 +1,311 @@
-use tokio::time::Interval;
+use std::{collections::HashMap, time::Duration};
 
-use crate::{item_generator::ItemGenerator, metrics::Metrics};
+use aws_sdk_dynamodb::{
+    operation::{get_item::GetItemOutput, put_item::builders::PutItemFluentBuilder},
+    types::{AttributeValue, ReturnConsumedCapacity},
+};
+use rand::SeedableRng;
+use tokio::{
+    sync::watch,
+    task::{JoinError, JoinSet},
+    time::Interval,
+};
 
+use crate::{
+    batches::{batch_get, batch_write},
+    cache_status_interceptor::{CacheStatus, CacheStatusInterceptor},
+    control::RunSettings,
+    item_generator::ItemGenerator,
+    metrics::{Metrics, Operation},
+    mix::{MixOperation, OperationOptions},
+    pagination::{query, scan},
+    partiql::{Statement, batch_select, execute_statement},
+    read_modify_write::read_modify_write,
+    transactions::{transact_get, transact_write},
+    ttl::Ttl,
+};
+
+/// Sends this worker's share of the target rate until the run is stopped or the run settings go
+/// away, then waits for the requests it started.
 pub async fn load_generator_task(
     client: aws_sdk_dynamodb::Client,
-    mut rate_limiter: Interval,
+    threads: usize,
+    mut settings: watch::Receiver<RunSettings>,
     mut item_generator: ItemGenerator,
     metrics: Metrics,
+    options: OperationOptions,
 ) {
-    loop {
-        rate_limiter.tick().await;
-
-        tokio::spawn(run_get_command(
-            client.clone(),
-            metrics.clone(),
-            aws_sdk_dynamodb::types::AttributeValue::S(item_generator.next()),
-        ));
+    // The mix doesn't need to be reproducible, and workers shouldn't choose in lockstep.
+    let mut random = rand::rngs::SmallRng::from_os_rng();
+    let mut requests = JoinSet::new();
+    'run: loop {
+        let current = settings.borrow_and_update().clone();
+        if current.stopped {
+            break;
+        }
+        if current.paused || current.tps == 0 {
+            if settings.changed().await.is_err() {
+                break;
+            }
+            continue;
+        }
+
+        let mut rate_limiter = rate_limiter(current.tps, threads);
+        loop {
+            tokio::select! {
+                _ = rate_limiter.tick() => {
+                    let operation = current.mix.choose(&mut random);
+                    start_operation(operation, &client, &metrics, &mut item_generator, &options, &mut requests);
+                }
+                Some(finished) = requests.join_next(), if !requests.is_empty() => {
+                    log_failure(finished);
+                }
+                changed = settings.changed() => {
+                    if changed.is_err() {
+                        break 'run;
+                    }
+                    break;
+                }
+            }
+        }
     }
+    // The requests in flight are part of the run, so they finish before it is reported
+    while let Some(finished) = requests.join_next().await {
+        log_failure(finished);
+    }
+}
+
+fn log_failure(finished: Result<(), JoinError>) {
+    if let Err(e) = finished {
+        log::error!("a request task failed: {e}");
+    }
+}
+
+/// Spawn one operation of the mix into `requests`, with keys from the item generator.
+fn start_operation(
+    operation: MixOperation,
+    client: &aws_sdk_dynamodb::Client,
+    metrics: &Metrics,
+    item_generator: &mut ItemGenerator,
+    options: &OperationOptions,
+    requests: &mut JoinSet<()>,
+) {
+    let client = client.clone();
+    let metrics = metrics.clone();
+    match operation {
+        MixOperation::Get => {
+            let user_id = AttributeValue::S(item_generator.next());
+            requests.spawn(run_get_command(
+                client,
+                metrics,
+                user_id,
+                options.ttl.clone(),
+            ));
+        }
+        MixOperation::Put => {
+            let user_id = AttributeValue::S(item_generator.next());
+            requests.spawn(run_put_command(
+                client,
+                metrics,
+                user_id,
+                options.ttl.clone(),
+            ));
+        }
+        MixOperation::ReadModifyWrite => {
+            let user_id = AttributeValue::S(item_generator.next());
+            requests.spawn(read_modify_write(
+                client,
+                metrics,
+                user_id,
+                options.read_modify_write,
+                options.ttl.clone(),
+            ));
+        }
+        MixOperation::TransactGet => {
+            let keys = distinct_keys(item_generator, options.transaction.items);
+            requests.spawn(transact_get(client, metrics, keys));
+        }
+        MixOperation::TransactWrite => {
+            let keys = distinct_keys(item_generator, options.transaction.items);
+            requests.spawn(transact_write(client, metrics, keys, options.ttl.clone()));
+        }
+        MixOperation::Query => {
+            let partition = AttributeValue::S(item_generator.next());
+            requests.spawn(query(client, metrics, partition, options.query.clone()));
+        }
+        MixOperation::Scan => {
+            requests.spawn(scan(client, metrics, options.scan.clone()));
+        }
+        MixOperation::BatchGet => {
+            let keys = distinct_keys(item_generator, options.batch.get_items);
+            requests.spawn(batch_get(client, metrics, keys, options.batch));
+        }
+        MixOperation::BatchWrite => {
+            let keys = distinct_keys(item_generator, options.batch.write_items);
+            requests.spawn(batch_write(
+                client,
+                metrics,
+                keys,
+                options.batch,
+                options.ttl.clone(),
+            ));
+        }
+        MixOperation::PartiqlSelect | MixOperation::PartiqlInsert | MixOperation::PartiqlUpdate => {
+            let statement = match operation {
+                MixOperation::PartiqlInsert => Statement::Insert,
+                MixOperation::PartiqlUpdate => Statement::Update,
+                _ => Statement::Select,
+            };
+            let user_id = AttributeValue::S(item_generator.next());
+            requests.spawn(execute_statement(
+                client,
+                metrics,
+                statement,
+                user_id,
+                options.partiql,
+                options.ttl.clone(),
+            ));
+        }
+        MixOperation::PartiqlBatch => {
+            let keys = distinct_keys(item_generator, options.partiql.batch_statements);
+            requests.spawn(batch_select(client, metrics, keys, options.partiql));
+        }
+    }
+}
+
+fn distinct_keys(item_generator: &mut ItemGenerator, count: usize) -> Vec<AttributeValue> {
+    item_generator
+        .next_distinct(count)
+        .into_iter()
+        .map(AttributeValue::S)
+        .collect()
+}
+
+fn rate_limiter(tps: u32, threads: usize) -> Interval {
+    let mut rate_limiter = tokio::time::interval((Duration::from_secs(1) / tps) * threads as u32);
+    rate_limiter.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    rate_limiter
 }
 
 async fn run_get_command(
     client: aws_sdk_dynamodb::Client,
     metrics: Metrics,
-    user_id: aws_sdk_dynamodb::types::AttributeValue,
+    user_id: AttributeValue,
+    ttl: Option<Ttl>,
 ) {
+    let _in_flight = metrics.start_request();
+    let Some(reply) = get_user(&client, &metrics, user_id.clone(), false).await else {
+        return;
+    };
+    match (&reply.output.item, &ttl) {
+        (None, _) => put_item(&client, &metrics, user_id, ttl.as_ref()).await,
+        (Some(item), Some(ttl)) if ttl.expired(item) => {
+            metrics.record_expired_read(Operation::GetItem, reply.cache_status)
+        }
+        _ => (),
+    }
+}
+
+/// A completed GetItem, and how it was served.
+pub struct GetReply {
+    pub output: GetItemOutput,
+    pub cache_status: CacheStatus,
+    pub latency: Duration,
+}
+
+/// GetItem a user, and record its latency, cache status and consumed capacity.
+/// Errors are recorded and logged, and return None.
+pub async fn get_user(
+    client: &aws_sdk_dynamodb::Client,
+    metrics: &Metrics,
+    user_id: AttributeValue,
+    consistent_read: bool,
+) -> Option<GetReply> {
+    let cache_status = CacheStatusInterceptor::default();
     let start = std::time::Instant::now();
-    let reply = client
+    let output = match client
         .get_item()
         .table_name("users")
-        .key("user", user_id.clone())
+        .key("user", user_id)
+        .consistent_read(consistent_read)
+        .return_consumed_capacity(ReturnConsumedCapacity::Total)
+        .customize()
+        .interceptor(cache_status.clone())
         .send()
         .await
-        .expect("must be able to send request");
-    metrics.record_latency(start.elapsed());
-    log::debug!("reply: {reply:?}");
-    if reply.item.is_none() {
-        let request = client
-            .put_item()
-            .table_name("users")
-            .item("user", user_id)
-            .item(
-                "value",
-                aws_sdk_dynamodb::types::AttributeValue::S("test".to_string()),
-            );
-        match request.send().await {
-            Ok(_) => (),
-            Err(e) => {
-                log::error!("failed to put item: {e:#?}");
-            }
+    {
+        Ok(output) => output,
+        Err(e) => {
+            log::error!("failed to get item: {e:#?}");
+            metrics.record_error(Operation::GetItem, &e);
+            return None;
+        }
+    };
+    let latency = start.elapsed();
+    let cache_status = cache_status.status();
+    metrics.record_latency(Operation::GetItem, latency, cache_status);
+    // A cache hit replays the consumed capacity of the original read, but it didn't cost anything.
+    if cache_status != CacheStatus::Hit {
+        metrics.record_read_capacity(output.consumed_capacity());
+    }
+    log::debug!("reply: {output:?}");
+    Some(GetReply {
+        output,
+        cache_status,
+        latency,
+    })
+}
+
+async fn run_put_command(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    user_id: AttributeValue,
+    ttl: Option<Ttl>,
+) {
+    let _in_flight = metrics.start_request();
+    put_item(&client, &metrics, user_id, ttl.as_ref()).await;
+}
+
+/// The test item for a user, with an expiry if there is a ttl.
+pub fn user_item(user_id: AttributeValue, ttl: Option<&Ttl>) -> HashMap<String, AttributeValue> {
+    let mut item = HashMap::from([
+        ("user".to_string(), user_id),
+        ("value".to_string(), AttributeValue::S("test".to_string())),
+    ]);
+    item.extend(ttl.map(Ttl::expiry));
+    item
+}
+
+/// A PutItem of the test item for a user.
+pub fn put_user(
+    client: &aws_sdk_dynamodb::Client,
+    user_id: AttributeValue,
+    ttl: Option<&Ttl>,
+) -> PutItemFluentBuilder {
+    client
+        .put_item()
+        .table_name("users")
+        .set_item(Some(user_item(user_id, ttl)))
+}
+
+/// PutItem a user, and record its latency and consumed capacity.
+pub async fn put_item(
+    client: &aws_sdk_dynamodb::Client,
+    metrics: &Metrics,
+    user_id: AttributeValue,
+    ttl: Option<&Ttl>,
+) {
+    let request =
+        put_user(client, user_id, ttl).return_consumed_capacity(ReturnConsumedCapacity::Total);
+    let start = std::time::Instant::now();
+    match request.send().await {
+        Ok(reply) => {
+            metrics.record_latency(Operation::PutItem, start.elapsed(), CacheStatus::Absent);
+            metrics.record_write_capacity(reply.consumed_capacity());
+        }
+        Err(e) => {
+            log::error!("failed to put item: {e:#?}");
+            metrics.record_error(Operation::PutItem, &e);
         }
     }
 }
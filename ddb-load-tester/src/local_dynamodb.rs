@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use crate::args::LocalArgs;
+
+/// Serve the local DynamoDB stand-in until it is interrupted.
+pub async fn serve(args: &LocalArgs) {
+    let config = ddb_local::Config {
+        tables: args.table.clone(),
+        latency: Duration::from_millis(args.latency_ms),
+        latency_jitter: Duration::from_millis(args.latency_jitter_ms),
+        error_rate: args.error_rate,
+        throttle_rate: args.throttle_rate,
+        unprocessed_rate: args.unprocessed_rate,
+        credentials: args
+            .access_key_id
+            .as_ref()
+            .zip(args.secret_access_key.as_ref())
+            .map(|(access_key_id, secret)| ddb_local::Credentials::new(access_key_id, secret)),
+    };
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .expect("must be able to bind the stand-in listener");
+    log::info!(
+        "serving a local DynamoDB stand-in on http://{} with tables {:?}, {}",
+        args.listen,
+        config
+            .tables
+            .iter()
+            .map(|table| &table.name)
+            .collect::<Vec<_>>(),
+        if config.credentials.is_some() {
+            "checking signatures"
+        } else {
+            "not checking signatures"
+        },
+    );
+    tokio::select! {
+        result = ddb_local::serve(listener, config) => result.expect("the stand-in must not fail"),
+        _ = tokio::signal::ctrl_c() => log::info!("interrupted"),
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
+};
+
+/// Sub-buckets per power of 2. 8 gives about 9% relative precision.
+const BUCKETS_PER_OCTAVE: usize = 8;
+/// Octaves above 1 microsecond. 2^28 microseconds is a bit under 4.5 minutes.
+const OCTAVES: usize = 28;
+pub const BUCKET_COUNT: usize = BUCKETS_PER_OCTAVE * OCTAVES + 1;
+
+/// The in-process registry the tester records into alongside goodmetrics.
+///
+/// goodmetrics gauges are reset every time they are reported, so anything that reads
+/// metrics locally (like a scrape endpoint) reads these cumulative copies instead.
+pub fn default_registry() -> &'static Registry {
+    static DEFAULT_REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::default);
+
+    &DEFAULT_REGISTRY
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SeriesKey {
+    pub name: &'static str,
+    pub labels: Vec<(&'static str, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Series {
+    Counter(Arc<AtomicU64>),
+    Gauge(Arc<AtomicI64>),
+    Histogram(Arc<LocalHistogram>),
+}
+
+/// Something that happened during the run, like a rate change, to line up with the metrics.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Annotation {
+    pub timestamp_ms: u64,
+    pub kind: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Registry {
+    series: Mutex<BTreeMap<SeriesKey, Series>>,
+    annotations: Mutex<Vec<Annotation>>,
+}
+impl Registry {
+    pub fn annotate(&self, annotation: Annotation) {
+        self.annotations
+            .lock()
+            .expect("local mutex works")
+            .push(annotation);
+    }
+
+    /// Annotations after the first `skip`, for readers that keep their place.
+    pub fn annotations(&self, skip: usize) -> Vec<Annotation> {
+        self.annotations
+            .lock()
+            .expect("local mutex works")
+            .iter()
+            .skip(skip)
+            .cloned()
+            .collect()
+    }
+
+    /// Get or register a counter. Cache the handle.
+    pub fn counter(
+        &self,
+        name: &'static str,
+        labels: Vec<(&'static str, String)>,
+    ) -> Arc<AtomicU64> {
+        match self.get_or_insert(name, labels, || Series::Counter(Default::default())) {
+            Series::Counter(counter) => counter,
+            other => panic!("{name} is already registered as {other:?}"),
+        }
+    }
+
+    /// Get or register a gauge. Cache the handle.
+    pub fn gauge(&self, name: &'static str, labels: Vec<(&'static str, String)>) -> Arc<AtomicI64> {
+        match self.get_or_insert(name, labels, || Series::Gauge(Default::default())) {
+            Series::Gauge(gauge) => gauge,
+            other => panic!("{name} is already registered as {other:?}"),
+        }
+    }
+
+    /// Get or register a histogram. Cache the handle.
+    pub fn histogram(
+        &self,
+        name: &'static str,
+        labels: Vec<(&'static str, String)>,
+    ) -> Arc<LocalHistogram> {
+        match self.get_or_insert(name, labels, || Series::Histogram(Default::default())) {
+            Series::Histogram(histogram) => histogram,
+            other => panic!("{name} is already registered as {other:?}"),
+        }
+    }
+
+    /// The current value of every series, for sinks that compute intervals or summaries.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            series: self
+                .series()
+                .into_iter()
+                .map(|(key, series)| SeriesSnapshot {
+                    name: key.name.to_string(),
+                    labels: key
+                        .labels
+                        .into_iter()
+                        .map(|(name, value)| (name.to_string(), value))
+                        .collect(),
+                    value: match series {
+                        Series::Counter(counter) => {
+                            SeriesValue::Counter(counter.load(Ordering::Relaxed))
+                        }
+                        Series::Gauge(gauge) => SeriesValue::Gauge(gauge.load(Ordering::Relaxed)),
+                        Series::Histogram(histogram) => {
+                            SeriesValue::Histogram(histogram.snapshot())
+                        }
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    /// All registered series, sorted by name and then labels.
+    pub fn series(&self) -> Vec<(SeriesKey, Series)> {
+        self.series
+            .lock()
+            .expect("local mutex works")
+            .iter()
+            .map(|(key, series)| (key.clone(), series.clone()))
+            .collect()
+    }
+
+    fn get_or_insert(
+        &self,
+        name: &'static str,
+        mut labels: Vec<(&'static str, String)>,
+        default: impl FnOnce() -> Series,
+    ) -> Series {
+        labels.sort();
+        self.series
+            .lock()
+            .expect("local mutex works")
+            .entry(SeriesKey { name, labels })
+            .or_insert_with(default)
+            .clone()
+    }
+}
+
+/// A cumulative latency histogram with fixed, exponentially sized buckets.
+///
+/// Fixed buckets keep snapshots cheap to subtract and merge.
+#[derive(Debug)]
+pub struct LocalHistogram {
+    buckets: Box<[AtomicU64]>,
+    sum_nanos: AtomicU64,
+}
+impl Default for LocalHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKET_COUNT).map(|_| AtomicU64::new(0)).collect(),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+}
+impl LocalHistogram {
+    pub fn observe(&self, nanos: u64) {
+        self.buckets[bucket_index(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            counts: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            sum_nanos: self.sum_nanos.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// An owned copy of the registry at a point in time.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub series: Vec<SeriesSnapshot>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SeriesSnapshot {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: SeriesValue,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesValue {
+    Counter(u64),
+    Gauge(i64),
+    Histogram(HistogramSnapshot),
+}
+
+impl Snapshot {
+    /// What happened between `earlier` and this snapshot. Gauges keep their current value.
+    pub fn since(&self, earlier: &Snapshot) -> Snapshot {
+        Snapshot {
+            series: self
+                .series
+                .iter()
+                .map(|series| {
+                    let earlier = earlier
+                        .series
+                        .iter()
+                        .find(|e| e.name == series.name && e.labels == series.labels)
+                        .map(|e| &e.value);
+                    let value = match (&series.value, earlier) {
+                        (SeriesValue::Counter(now), Some(SeriesValue::Counter(then))) => {
+                            SeriesValue::Counter(now.saturating_sub(*then))
+                        }
+                        (SeriesValue::Histogram(now), Some(SeriesValue::Histogram(then))) => {
+                            SeriesValue::Histogram(now.since(then))
+                        }
+                        (value, _) => value.clone(),
+                    };
+                    SeriesSnapshot {
+                        name: series.name.clone(),
+                        labels: series.labels.clone(),
+                        value,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Add another process's snapshot into this one. Counters and gauges add up, histograms merge.
+    pub fn merge(&mut self, other: &Snapshot) {
+        for theirs in &other.series {
+            let Some(mine) = self
+                .series
+                .iter_mut()
+                .find(|mine| mine.name == theirs.name && mine.labels == theirs.labels)
+            else {
+                self.series.push(theirs.clone());
+                continue;
+            };
+            match (&mut mine.value, &theirs.value) {
+                (SeriesValue::Counter(mine), SeriesValue::Counter(theirs)) => *mine += theirs,
+                (SeriesValue::Gauge(mine), SeriesValue::Gauge(theirs)) => *mine += theirs,
+                (SeriesValue::Histogram(mine), SeriesValue::Histogram(theirs)) => {
+                    mine.merge(theirs)
+                }
+                (mine, theirs) => panic!("can't merge {theirs:?} into {mine:?}"),
+            }
+        }
+    }
+
+    /// Sum of the counters named `name` whose labels match `filter`.
+    pub fn counter(&self, name: &str, filter: impl Fn(&BTreeMap<String, String>) -> bool) -> u64 {
+        self.matching(name, filter)
+            .map(|value| match value {
+                SeriesValue::Counter(count) => *count,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Sum of the gauges named `name` whose labels match `filter`.
+    pub fn gauge(&self, name: &str, filter: impl Fn(&BTreeMap<String, String>) -> bool) -> i64 {
+        self.matching(name, filter)
+            .map(|value| match value {
+                SeriesValue::Gauge(value) => *value,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The merged histograms named `name` whose labels match `filter`.
+    pub fn histogram(
+        &self,
+        name: &str,
+        filter: impl Fn(&BTreeMap<String, String>) -> bool,
+    ) -> HistogramSnapshot {
+        let mut merged = HistogramSnapshot::default();
+        for value in self.matching(name, filter) {
+            if let SeriesValue::Histogram(histogram) = value {
+                merged.merge(histogram);
+            }
+        }
+        merged
+    }
+
+    /// Every value of `label` across the series named `name`.
+    pub fn label_values(&self, name: &str, label: &str) -> BTreeSet<String> {
+        self.series
+            .iter()
+            .filter(|series| series.name == name)
+            .filter_map(|series| series.labels.get(label).cloned())
+            .collect()
+    }
+
+    fn matching(
+        &self,
+        name: &str,
+        filter: impl Fn(&BTreeMap<String, String>) -> bool,
+    ) -> impl Iterator<Item = &SeriesValue> {
+        self.series
+            .iter()
+            .filter(move |series| series.name == name && filter(&series.labels))
+            .map(|series| &series.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HistogramSnapshot {
+    /// Observation count per bucket. See [`bucket_upper_bound`].
+    pub counts: Vec<u64>,
+    pub sum_nanos: u64,
+}
+impl Default for HistogramSnapshot {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKET_COUNT],
+            sum_nanos: 0,
+        }
+    }
+}
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn mean(&self) -> u64 {
+        self.sum_nanos.checked_div(self.count()).unwrap_or_default()
+    }
+
+    /// The upper bound of the bucket holding the `quantile` observation, in nanoseconds.
+    /// 0 if the histogram is empty.
+    pub fn percentile(&self, quantile: f64) -> u64 {
+        let count = self.count();
+        if count == 0 {
+            return 0;
+        }
+        let rank = ((quantile * count as f64).ceil() as u64).clamp(1, count);
+        let mut cumulative = 0;
+        for (index, bucket) in self.counts.iter().enumerate() {
+            cumulative += bucket;
+            if rank <= cumulative {
+                return bucket_upper_bound(index);
+            }
+        }
+        bucket_upper_bound(self.counts.len() - 1)
+    }
+
+    pub fn max(&self) -> u64 {
+        self.counts
+            .iter()
+            .rposition(|count| 0 < *count)
+            .map(bucket_upper_bound)
+            .unwrap_or_default()
+    }
+
+    pub fn merge(&mut self, other: &HistogramSnapshot) {
+        for (mine, theirs) in self.counts.iter_mut().zip(&other.counts) {
+            *mine += theirs;
+        }
+        self.sum_nanos += other.sum_nanos;
+    }
+
+    pub fn since(&self, earlier: &HistogramSnapshot) -> HistogramSnapshot {
+        HistogramSnapshot {
+            counts: self
+                .counts
+                .iter()
+                .zip(&earlier.counts)
+                .map(|(now, then)| now.saturating_sub(*then))
+                .collect(),
+            sum_nanos: self.sum_nanos.saturating_sub(earlier.sum_nanos),
+        }
+    }
+}
+
+/// Inclusive upper bound of a bucket, in nanoseconds. Bucket 0 holds everything up to 1 microsecond.
+pub fn bucket_upper_bound(index: usize) -> u64 {
+    (1000.0 * 2f64.powf(index as f64 / BUCKETS_PER_OCTAVE as f64)).round() as u64
+}
+
+fn bucket_index(nanos: u64) -> usize {
+    let micros = nanos as f64 / 1000.0;
+    if micros <= 1.0 {
+        0
+    } else {
+        ((micros.log2() * BUCKETS_PER_OCTAVE as f64).ceil() as usize).min(BUCKET_COUNT - 1)
+    }
+}
@@ -1,23 +1,65 @@
-use std::{sync::atomic::AtomicUsize, time::Duration};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, atomic::AtomicUsize},
+    time::Duration,
+};
 
-use args::Args;
+use args::{Args, Command, DriftMode, RetryPolicy};
 use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::config::{
+    retry::{RetryConfig, RetryMode},
+    timeout::TimeoutConfig,
+};
 use clap::Parser;
+use control::{RunControl, RunSettings};
+use cost::OnDemandPricing;
+use dashboard::Dashboard;
 use header_interceptor::HeaderInterceptor;
-use item_generator::ItemGenerator;
+use item_generator::{Drift, ItemGenerator};
 use load_generator_task::load_generator_task;
 use metrics::Metrics;
+use mix::OperationOptions;
 use proxy_interceptor::ProxyInterceptor;
 use proxy_interceptor_for_lambda::ProxyInterceptorForLambda;
+use results::ResultsWriter;
+use retry_interceptor::RetryInterceptor;
+use timing_interceptor::TimingInterceptor;
 use tokio::task::JoinSet;
+use worker::Worker;
 
 mod args;
+mod batches;
+mod cache_status_interceptor;
+mod compare;
+mod control;
+mod control_api;
+mod coordinator;
+mod cost;
+mod dashboard;
 mod header_interceptor;
+mod herd;
 mod item_generator;
 mod load_generator_task;
+mod local_dynamodb;
+mod local_metrics;
 mod metrics;
+mod mix;
+mod pagination;
+mod partiql;
+mod populate;
+mod prometheus;
+mod proxy_headers;
 mod proxy_interceptor;
 mod proxy_interceptor_for_lambda;
+mod read_modify_write;
+mod results;
+mod retry_interceptor;
+#[cfg(test)]
+mod test_support;
+mod timing_interceptor;
+mod transactions;
+mod ttl;
+mod worker;
 
 fn main() {
     let args = Args::parse();
@@ -27,8 +69,26 @@ fn main() {
             .default_filter_or("debug")
             .default_write_style_or("always"),
     );
+    if args.tui {
+        // The dashboard owns the terminal
+        logger_builder.target(env_logger::Target::Pipe(Box::new(
+            std::fs::File::create(&args.tui_log_file).expect("must be able to create the log file"),
+        )));
+        logger_builder.write_style(env_logger::WriteStyle::Never);
+    }
     logger_builder.init();
 
+    if let Some(Command::Compare(compare_args)) = &args.command {
+        let regressed = compare::compare(compare_args);
+        std::process::exit(if regressed { 1 } else { 0 });
+    }
+    if let Some(Command::Local(local_args)) = &args.command {
+        tokio::runtime::Runtime::new()
+            .expect("must be able to build a runtime")
+            .block_on(local_dynamodb::serve(local_args));
+        return;
+    }
+
     tokio::runtime::Builder::new_multi_thread()
         .worker_threads(args.threads)
         .enable_all()
@@ -44,9 +104,38 @@ fn main() {
         .block_on(amain(args))
 }
 
-async fn amain(args: Args) {
+async fn amain(mut args: Args) {
+    if !args.coordinate.is_empty() {
+        coordinator::coordinate(&args).await;
+        return;
+    }
+    let mut worker = match args.worker_listen {
+        Some(listen) => {
+            let (worker, assignment) = Worker::start(listen).await;
+            assignment.apply(&mut args);
+            Some(worker)
+        }
+        None => None,
+    };
+
+    // Bound here rather than in the serving tasks, so a port in use stops the run
+    let prometheus = match args.prometheus_listen {
+        Some(listen) => Some(bind_or_exit(listen, "prometheus metrics").await),
+        None => None,
+    };
+    let control_listener = match args.control_listen {
+        Some(listen) => Some(bind_or_exit(listen, "the control api").await),
+        None => None,
+    };
+    let metrics = Metrics::configure(&args, prometheus);
+
     let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let mut config = aws_sdk_dynamodb::config::Builder::from(&config);
+    let timeout_config = timeout_config(&args, config.timeout_config());
+    let mut config = aws_sdk_dynamodb::config::Builder::from(&config)
+        .retry_config(retry_config(&args, config.retry_config()))
+        .timeout_config(timeout_config)
+        .interceptor(TimingInterceptor::new(metrics.clone()))
+        .interceptor(RetryInterceptor::new(metrics.clone()));
     if let Some(service_log) = &args.service_log {
         log::info!("using service log level: {service_log}");
         config = config.interceptor(HeaderInterceptor::new(
@@ -63,39 +152,257 @@ async fn amain(args: Args) {
             .unwrap_or_default()
         {
             // lambda requires a special case interceptor
-            config.interceptor(ProxyInterceptorForLambda::new(accelerator_url.clone()))
+            config.interceptor(ProxyInterceptorForLambda::new(
+                accelerator_url.clone(),
+                args.lambda_encoding,
+            ))
         } else {
             config.interceptor(ProxyInterceptor::new(
                 accelerator_url.clone(),
-                "x-momento-authorization".into(),
-                std::env::var("MOMENTO_AUTH_TOKEN")
-                    .expect("must set MOMENTO_AUTH_TOKEN")
-                    .into(),
+                args.proxy_target_header.clone().into(),
+                proxy_headers::proxy_headers(&args),
             ))
         }
     } else {
         config
     };
     let config = config.build();
-    let metrics = Metrics::configure(&args);
 
-    let item_generator = ItemGenerator::new(args.seed, args.items, args.item_key_length);
+    assert!(
+        args.shard_index < args.shard_count,
+        "--shard-index must be less than --shard-count"
+    );
+    assert!(
+        0.0 <= args.shard_overlap,
+        "--shard-overlap must not be negative"
+    );
+    // Overlap only applies to the finest shards
+    let (process_overlap, worker_overlap) = if args.partition_workers {
+        (0.0, args.shard_overlap)
+    } else {
+        (args.shard_overlap, 0.0)
+    };
+    let item_generator = ItemGenerator::new(args.seed, args.items, args.item_key_length).shard(
+        args.shard_index,
+        args.shard_count,
+        process_overlap,
+    );
+    if args.populate {
+        populate::populate(
+            aws_sdk_dynamodb::Client::from_conf(config.clone()),
+            item_generator.owned(),
+            args.populate_concurrency,
+            ttl::Ttl::new(&args),
+        )
+        .await;
+    }
+
+    // Workers start together once all of them have populated
+    if let Some(worker) = &mut worker {
+        worker.ready().await;
+    }
+    let started = std::time::Instant::now();
+    let started_at_ms = results::unix_millis();
+    let mut results = args.results_dir.as_deref().map(ResultsWriter::create);
+    let control = RunControl::new(
+        RunSettings {
+            tps: args.tps,
+            paused: false,
+            stopped: false,
+            mix: args.mix.clone(),
+        },
+        metrics.clone(),
+    );
+    let pricing = OnDemandPricing::new(&args);
+    if let Some(listener) = control_listener {
+        let summarize: control_api::Summarize = Arc::new({
+            let metrics = metrics.clone();
+            let control = control.clone();
+            move || {
+                results::summarize(
+                    &metrics,
+                    pricing,
+                    started,
+                    started_at_ms,
+                    control.settings().tps,
+                )
+            }
+        });
+        tokio::spawn(control_api::serve(
+            listener,
+            control.clone(),
+            metrics.clone(),
+            started,
+            summarize,
+        ));
+    }
+    assert!(
+        0.0 < args.working_set && args.working_set <= 1.0,
+        "--working-set must be a fraction above 0 and at most 1"
+    );
+    assert!(
+        args.drift_period.is_none_or(|period| 0.0 < period),
+        "--drift-period must be above 0"
+    );
+    let drift = args.drift_period.map(|period| Drift {
+        started,
+        period: Duration::from_secs_f64(period),
+        slide: matches!(args.drift, DriftMode::Slide),
+    });
+    let operation_options = OperationOptions::new(&args);
     let mut set = JoinSet::new();
-    for _ in 0..args.threads {
-        let mut rate_limiter =
-            tokio::time::interval((Duration::from_secs(1) / args.tps) * args.threads as u32);
-        rate_limiter.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-        let client = aws_sdk_dynamodb::Client::from_conf(config.clone());
-        set.spawn(load_generator_task(
-            client,
-            rate_limiter,
-            item_generator.clone(),
+    if let Some(herd_size) = args.herd_size {
+        set.spawn(herd::herd_task(
+            aws_sdk_dynamodb::Client::from_conf(config.clone()),
             metrics.clone(),
+            herd_size,
+            Duration::from_secs(args.herd_interval),
+            started_at_ms,
         ));
+    } else {
+        for thread in 0..args.threads {
+            let client = aws_sdk_dynamodb::Client::from_conf(config.clone());
+            let item_generator = if args.partition_workers {
+                item_generator
+                    .clone()
+                    .shard(thread as u64, args.threads as u64, worker_overlap)
+            } else {
+                item_generator.clone()
+            }
+            .working_set(args.working_set, drift)
+            // Unique across the threads of every process, so none pick the same keys in lockstep
+            .stream(args.shard_index * args.threads as u64 + thread as u64);
+            set.spawn(load_generator_task(
+                client,
+                args.threads,
+                control.subscribe(),
+                item_generator,
+                metrics.clone(),
+                operation_options.clone(),
+            ));
+        }
+    }
+    let dashboard = args.tui.then(|| Dashboard::start(control.clone(), started));
+
+    let deadline = async {
+        match args.duration {
+            Some(duration) => tokio::time::sleep(Duration::from_secs(duration)).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(deadline);
+    let mut results_interval = tokio::time::interval(Duration::from_secs(args.results_interval));
+    results_interval.tick().await;
+    loop {
+        tokio::select! {
+            joined = set.join_next() => {
+                joined.expect("it should join").expect("it should succeed");
+                break;
+            }
+            _ = &mut deadline => {
+                log::info!("run duration elapsed");
+                break;
+            }
+            _ = tokio::signal::ctrl_c() => {
+                log::info!("interrupted");
+                break;
+            }
+            _ = async { dashboard.as_ref().expect("checked").quit_requested().await }, if dashboard.is_some() => {
+                log::info!("quit from the dashboard");
+                break;
+            }
+            _ = results_interval.tick(), if results.is_some() => {
+                if let Some(results) = &mut results {
+                    results.record_interval(
+                        local_metrics::default_registry().snapshot(),
+                        started,
+                        control.settings().tps,
+                    );
+                }
+            }
+        }
+    }
+    // Stop starting requests, and wait for the ones in flight so the report has all of them
+    control.stop();
+    if args.herd_size.is_some() {
+        set.shutdown().await;
+    } else {
+        while let Some(joined) = set.join_next().await {
+            joined.expect("it should succeed");
+        }
+    }
+    if let Some(dashboard) = dashboard {
+        dashboard.stop().await;
     }
 
-    set.join_next()
-        .await
-        .expect("it should join")
-        .expect("it should succeed");
+    cost::log_cost_report(&metrics, pricing);
+    let summary = results::summarize(
+        &metrics,
+        pricing,
+        started,
+        started_at_ms,
+        control.settings().tps,
+    );
+    if let Some(rmw) = &summary.read_modify_write {
+        log::info!(
+            "read-modify-writes: {} succeeded, {} exhausted, {} failed, {} retries, {:.1}% conditional check failures, success p50 {:.3}ms p99 {:.3}ms",
+            rmw.succeeded,
+            rmw.exhausted,
+            rmw.failed,
+            rmw.retries,
+            rmw.conditional_check_failure_rate * 100.0,
+            rmw.success_p50_ms,
+            rmw.success_p99_ms,
+        );
+    }
+    if let Some(results) = results {
+        results.finish(&summary);
+    }
+    if let Some(worker) = worker {
+        worker.finish().await;
+    }
+}
+
+/// Bind a listener for something the run serves, or exit with an error.
+async fn bind_or_exit(listen: SocketAddr, serving: &str) -> tokio::net::TcpListener {
+    match tokio::net::TcpListener::bind(listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("can't serve {serving} on {listen}: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Layered over the default retry config, so AWS_RETRY_MODE, AWS_MAX_ATTEMPTS and the profile
+/// still apply to what isn't set on the command line.
+fn retry_config(args: &Args, defaults: Option<&RetryConfig>) -> RetryConfig {
+    let defaults = defaults.cloned().unwrap_or_else(RetryConfig::standard);
+    let retry_config = match args.retry_mode {
+        None => defaults,
+        Some(RetryPolicy::Standard) => defaults.with_retry_mode(RetryMode::Standard),
+        Some(RetryPolicy::Adaptive) => defaults.with_retry_mode(RetryMode::Adaptive),
+        Some(RetryPolicy::Disabled) => {
+            if args.max_attempts.is_some() {
+                log::warn!("--max-attempts is ignored because retries are disabled");
+            }
+            return defaults.with_max_attempts(1);
+        }
+    };
+    match args.max_attempts {
+        Some(max_attempts) => retry_config.with_max_attempts(max_attempts),
+        None => retry_config,
+    }
+}
+
+/// Layered over the default timeouts, so the default connect timeout still applies.
+fn timeout_config(args: &Args, defaults: Option<&TimeoutConfig>) -> TimeoutConfig {
+    let mut timeout_config = defaults.map(TimeoutConfig::to_builder).unwrap_or_default();
+    if let Some(timeout) = args.operation_timeout_ms {
+        timeout_config = timeout_config.operation_timeout(Duration::from_millis(timeout));
+    }
+    if let Some(timeout) = args.operation_attempt_timeout_ms {
+        timeout_config = timeout_config.operation_attempt_timeout(Duration::from_millis(timeout));
+    }
+    timeout_config.build()
 }
@@ -1,38 +1,538 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
+use aws_sdk_dynamodb::{error::ProvideErrorMetadata, error::SdkError, types::ConsumedCapacity};
 use goodmetrics::GaugeDimensions;
 
-use crate::args::Args;
+use crate::{
+    args::Args, cache_status_interceptor::CacheStatus, local_metrics, prometheus,
+    read_modify_write::Outcome, results, timing_interceptor::Phase,
+};
+
+/// The dynamodb api calls the tester makes. This is the `operation` dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)] // named after the api calls
+pub enum Operation {
+    GetItem,
+    PutItem,
+    UpdateItem,
+    TransactGetItems,
+    TransactWriteItems,
+    Query,
+    Scan,
+    BatchGetItem,
+    BatchWriteItem,
+    ExecuteStatement,
+    BatchExecuteStatement,
+}
+impl Operation {
+    pub const ALL: [Operation; 11] = [
+        Operation::GetItem,
+        Operation::PutItem,
+        Operation::UpdateItem,
+        Operation::TransactGetItems,
+        Operation::TransactWriteItems,
+        Operation::Query,
+        Operation::Scan,
+        Operation::BatchGetItem,
+        Operation::BatchWriteItem,
+        Operation::ExecuteStatement,
+        Operation::BatchExecuteStatement,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::GetItem => "GetItem",
+            Operation::PutItem => "PutItem",
+            Operation::UpdateItem => "UpdateItem",
+            Operation::TransactGetItems => "TransactGetItems",
+            Operation::TransactWriteItems => "TransactWriteItems",
+            Operation::Query => "Query",
+            Operation::Scan => "Scan",
+            Operation::BatchGetItem => "BatchGetItem",
+            Operation::BatchWriteItem => "BatchWriteItem",
+            Operation::ExecuteStatement => "ExecuteStatement",
+            Operation::BatchExecuteStatement => "BatchExecuteStatement",
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Metrics {
-    latency: goodmetrics::HistogramHandle,
+    target: String,
+    /// Indexed by Operation
+    operations: Arc<[OperationMetrics]>,
+    serialization_latency: Histogram,
+    signing_latency: Histogram,
+    transmit_latency: Histogram,
+    deserialization_latency: Histogram,
+    retries: Counter,
+    read_capacity: Counter,
+    write_capacity: Counter,
+    in_flight: Gauge,
+    read_modify_write: Arc<ReadModifyWriteMetrics>,
+    query_pagination: PaginationMetrics,
+    scan_pagination: PaginationMetrics,
+    batch_get: BatchMetrics,
+    batch_write: BatchMetrics,
 }
 impl Metrics {
-    pub fn configure(args: &Args) -> Self {
+    /// `prometheus` is the bound `--prometheus-listen` listener, to serve the metrics on.
+    pub fn configure(args: &Args, prometheus: Option<tokio::net::TcpListener>) -> Self {
+        if let Some(listener) = prometheus {
+            tokio::spawn(prometheus::serve(listener));
+        }
         configure_metrics(args)
     }
 
-    pub fn record_latency(&self, amount: Duration) {
-        self.latency.observe(amount.as_nanos() as i64);
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn record_latency(
+        &self,
+        operation: Operation,
+        amount: Duration,
+        cache_status: CacheStatus,
+    ) {
+        let operation = &self.operations[operation as usize];
+        let metrics = match cache_status {
+            CacheStatus::Hit => &operation.cache_hit,
+            CacheStatus::Miss => &operation.cache_miss,
+            CacheStatus::Absent => &operation.cache_absent,
+        };
+        metrics.latency.observe(amount);
+        metrics.requests.add(1);
+    }
+
+    pub fn record_phase_latency(&self, phase: Phase, amount: Duration) {
+        let histogram = match phase {
+            Phase::Serialization => &self.serialization_latency,
+            Phase::Signing => &self.signing_latency,
+            Phase::Transmit => &self.transmit_latency,
+            Phase::Deserialization => &self.deserialization_latency,
+        };
+        histogram.observe(amount);
+    }
+
+    pub fn record_retry(&self) {
+        self.retries.add(1);
+    }
+
+    /// Record a whole read-modify-write, from the first read to the last write.
+    pub fn record_read_modify_write(&self, outcome: Outcome, retries: u32, amount: Duration) {
+        let metrics = &self.read_modify_write;
+        match outcome {
+            Outcome::Succeeded => {
+                metrics.succeeded.add(1);
+                metrics.success_latency.observe(amount);
+            }
+            Outcome::Exhausted => metrics.exhausted.add(1),
+            Outcome::Failed => metrics.failed.add(1),
+        }
+        metrics.retries.add(retries as u64);
+    }
+
+    /// Record a read-modify-write's conditional write losing the race to another writer.
+    pub fn record_read_modify_write_conflict(&self) {
+        self.read_modify_write.conflicts.add(1);
+    }
+
+    /// Record a whole paginated Query or Scan. Its pages are recorded like any other request.
+    pub fn record_pagination(&self, operation: Operation, pages: u32, amount: Duration) {
+        let metrics = match operation {
+            Operation::Query => &self.query_pagination,
+            Operation::Scan => &self.scan_pagination,
+            other => panic!("{} isn't paginated", other.as_str()),
+        };
+        metrics.latency.observe(amount);
+        metrics.pages.add(pages as u64);
+    }
+
+    /// Count the items of a whole batch, including its requests for unprocessed items.
+    /// `retried` counts an item each time it was unprocessed and requested again, and
+    /// `unprocessed` the items still unprocessed when it gave up.
+    pub fn record_batch(
+        &self,
+        operation: Operation,
+        items: usize,
+        retried: usize,
+        unprocessed: usize,
+    ) {
+        let metrics = match operation {
+            Operation::BatchGetItem => &self.batch_get,
+            Operation::BatchWriteItem => &self.batch_write,
+            other => panic!("{} isn't a batch", other.as_str()),
+        };
+        metrics.items.add(items as u64);
+        metrics.retried_items.add(retried as u64);
+        metrics.unprocessed_items.add(unprocessed as u64);
+    }
+
+    /// Count a thundering herd round: its requests, and how many of them reached DynamoDB.
+    pub fn record_herd_round(&self, requests: u64, reached_dynamodb: u64) {
+        let labels = vec![("target", self.target.clone())];
+        Counter::new("herd_rounds", labels.clone()).add(1);
+        Counter::new("herd_requests", labels.clone()).add(requests);
+        Counter::new("herd_dynamodb_requests", labels).add(reached_dynamodb);
+    }
+
+    /// Errors are rare, so their gauges are looked up on demand rather than cached.
+    pub fn record_error<E: ProvideErrorMetadata, R>(
+        &self,
+        operation: Operation,
+        error: &SdkError<E, R>,
+    ) {
+        let kind = match error {
+            SdkError::ConstructionFailure(_) => "construction",
+            SdkError::TimeoutError(_) => "timeout",
+            SdkError::DispatchFailure(_) => "dispatch",
+            SdkError::ResponseError(_) => "response",
+            SdkError::ServiceError(e) => e.err().code().unwrap_or("service"),
+            _ => "unknown",
+        };
+        Counter::new(
+            "errors",
+            vec![
+                ("target", self.target.clone()),
+                ("operation", operation.as_str().to_string()),
+                ("error", kind.to_string()),
+            ],
+        )
+        .add(1);
+    }
+
+    /// Count a read that found an item past its TTL, which DynamoDB hasn't deleted yet or a cache
+    /// still has.
+    pub fn record_expired_read(&self, operation: Operation, cache_status: CacheStatus) {
+        Counter::new(
+            "expired_reads",
+            vec![
+                ("target", self.target.clone()),
+                ("operation", operation.as_str().to_string()),
+                ("cache", cache_status.as_str().to_string()),
+            ],
+        )
+        .add(1);
+    }
+
+    /// Count a statement that failed within a successful batch, by its error code.
+    pub fn record_statement_error(&self, operation: Operation, code: &str) {
+        Counter::new(
+            "statement_errors",
+            vec![
+                ("target", self.target.clone()),
+                ("operation", operation.as_str().to_string()),
+                ("error", code.to_string()),
+            ],
+        )
+        .add(1);
+    }
+
+    /// Count why an item made its transaction cancel, like `ConditionalCheckFailed` or
+    /// `TransactionConflict`.
+    pub fn record_cancellation_reason(&self, operation: Operation, reason: &str) {
+        Counter::new(
+            "transaction_cancellation_reasons",
+            vec![
+                ("target", self.target.clone()),
+                ("operation", operation.as_str().to_string()),
+                ("reason", reason.to_string()),
+            ],
+        )
+        .add(1);
+    }
+
+    /// Capacity units are fractional, so they are recorded in thousandths of a unit.
+    pub fn record_read_capacity(&self, consumed: Option<&ConsumedCapacity>) {
+        self.read_capacity.add(to_milli_units(consumed));
+    }
+
+    /// Capacity units are fractional, so they are recorded in thousandths of a unit.
+    pub fn record_write_capacity(&self, consumed: Option<&ConsumedCapacity>) {
+        self.write_capacity.add(to_milli_units(consumed));
+    }
+
+    /// (read, write) capacity units consumed since the start of the run
+    pub fn consumed_capacity(&self) -> (f64, f64) {
+        (
+            self.read_capacity.total() as f64 / 1000.0,
+            self.write_capacity.total() as f64 / 1000.0,
+        )
+    }
+
+    /// Record a change to the run, like a new target rate. It is logged, counted by kind and
+    /// kept for the results time series.
+    pub fn annotate(&self, kind: &'static str, detail: String) {
+        log::info!("annotation {kind}: {detail}");
+        Counter::new(
+            "annotations",
+            vec![("target", self.target.clone()), ("kind", kind.to_string())],
+        )
+        .add(1);
+        local_metrics::default_registry().annotate(local_metrics::Annotation {
+            timestamp_ms: results::unix_millis(),
+            kind: kind.to_string(),
+            detail,
+        });
+    }
+
+    /// Counts a request as in flight until the returned guard is dropped.
+    pub fn start_request(&self) -> InFlightGuard {
+        self.in_flight.add(1);
+        InFlightGuard {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+pub struct InFlightGuard {
+    in_flight: Gauge,
+}
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.add(-1);
     }
 }
 
+#[derive(Clone)]
+struct PaginationMetrics {
+    latency: Histogram,
+    pages: Counter,
+}
+impl PaginationMetrics {
+    fn new(target: &str, operation: Operation) -> Self {
+        let labels = vec![
+            ("target", target.to_string()),
+            ("operation", operation.as_str().to_string()),
+        ];
+        Self {
+            latency: Histogram::new("paginated_latency", labels.clone()),
+            pages: Counter::new("pages", labels),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct BatchMetrics {
+    items: Counter,
+    retried_items: Counter,
+    unprocessed_items: Counter,
+}
+impl BatchMetrics {
+    fn new(target: &str, operation: Operation) -> Self {
+        let labels = vec![
+            ("target", target.to_string()),
+            ("operation", operation.as_str().to_string()),
+        ];
+        Self {
+            items: Counter::new("batch_items", labels.clone()),
+            retried_items: Counter::new("batch_retried_items", labels.clone()),
+            unprocessed_items: Counter::new("batch_unprocessed_items", labels),
+        }
+    }
+}
+
+struct ReadModifyWriteMetrics {
+    success_latency: Histogram,
+    retries: Counter,
+    /// Conditional writes that lost the race. They aren't errors, so they are counted here
+    conflicts: Counter,
+    succeeded: Counter,
+    exhausted: Counter,
+    failed: Counter,
+}
+impl ReadModifyWriteMetrics {
+    fn new(target: &str) -> Self {
+        let outcome = |outcome: Outcome| {
+            Counter::new(
+                "read_modify_writes",
+                vec![
+                    ("target", target.to_string()),
+                    ("outcome", outcome.as_str().to_string()),
+                ],
+            )
+        };
+        Self {
+            success_latency: Histogram::new(
+                "read_modify_write_latency",
+                vec![("target", target.to_string())],
+            ),
+            retries: Counter::new(
+                "read_modify_write_retries",
+                vec![("target", target.to_string())],
+            ),
+            conflicts: Counter::new(
+                "read_modify_write_conflicts",
+                vec![("target", target.to_string())],
+            ),
+            succeeded: outcome(Outcome::Succeeded),
+            exhausted: outcome(Outcome::Exhausted),
+            failed: outcome(Outcome::Failed),
+        }
+    }
+}
+
+struct OperationMetrics {
+    cache_hit: CacheStatusMetrics,
+    cache_miss: CacheStatusMetrics,
+    cache_absent: CacheStatusMetrics,
+}
+impl OperationMetrics {
+    fn new(target: &str, operation: Operation) -> Self {
+        Self {
+            cache_hit: CacheStatusMetrics::new(target, operation, CacheStatus::Hit),
+            cache_miss: CacheStatusMetrics::new(target, operation, CacheStatus::Miss),
+            cache_absent: CacheStatusMetrics::new(target, operation, CacheStatus::Absent),
+        }
+    }
+}
+
+struct CacheStatusMetrics {
+    latency: Histogram,
+    requests: Counter,
+}
+impl CacheStatusMetrics {
+    fn new(target: &str, operation: Operation, cache_status: CacheStatus) -> Self {
+        let labels = vec![
+            ("target", target.to_string()),
+            ("operation", operation.as_str().to_string()),
+            ("cache", cache_status.as_str().to_string()),
+        ];
+        Self {
+            latency: Histogram::new("latency", labels.clone()),
+            requests: Counter::new("requests", labels),
+        }
+    }
+}
+
+/// A latency histogram, recorded both to goodmetrics and to the local registry.
+#[derive(Clone)]
+struct Histogram {
+    gauge: goodmetrics::HistogramHandle,
+    local: Arc<local_metrics::LocalHistogram>,
+}
+impl Histogram {
+    fn new(name: &'static str, labels: Vec<(&'static str, String)>) -> Self {
+        Self {
+            gauge: goodmetrics::default_gauge_factory().dimensioned_gauge_histogram(
+                "ddb_load_tester",
+                name,
+                GaugeDimensions::new(labels.clone()),
+            ),
+            local: local_metrics::default_registry().histogram(name, labels),
+        }
+    }
+
+    fn observe(&self, amount: Duration) {
+        let nanos = amount.as_nanos().min(i64::MAX as u128) as i64;
+        self.gauge.observe(nanos);
+        self.local.observe(nanos as u64);
+    }
+}
+
+/// A monotonic count, recorded both to goodmetrics and to the local registry.
+#[derive(Clone)]
+struct Counter {
+    gauge: goodmetrics::SumHandle,
+    local: Arc<AtomicU64>,
+}
+impl Counter {
+    fn new(name: &'static str, labels: Vec<(&'static str, String)>) -> Self {
+        Self {
+            gauge: goodmetrics::default_gauge_factory().dimensioned_gauge_sum(
+                "ddb_load_tester",
+                name,
+                GaugeDimensions::new(labels.clone()),
+            ),
+            local: local_metrics::default_registry().counter(name, labels),
+        }
+    }
+
+    fn add(&self, amount: u64) {
+        self.gauge.observe(amount as i64);
+        self.local.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    fn total(&self) -> u64 {
+        self.local.load(Ordering::Relaxed)
+    }
+}
+
+/// A current value. goodmetrics gets a statistic set of the values it took on.
+#[derive(Clone)]
+struct Gauge {
+    gauge: goodmetrics::StatisticSetHandle,
+    local: Arc<AtomicI64>,
+}
+impl Gauge {
+    fn new(name: &'static str, labels: Vec<(&'static str, String)>) -> Self {
+        Self {
+            gauge: goodmetrics::default_gauge_factory().dimensioned_gauge_statistic_set(
+                "ddb_load_tester",
+                name,
+                GaugeDimensions::new(labels.clone()),
+            ),
+            local: local_metrics::default_registry().gauge(name, labels),
+        }
+    }
+
+    fn add(&self, amount: i64) {
+        let value = self.local.fetch_add(amount, Ordering::Relaxed) + amount;
+        self.gauge.observe(value);
+    }
+}
+
+fn to_milli_units(consumed: Option<&ConsumedCapacity>) -> u64 {
+    consumed
+        .and_then(|consumed| consumed.capacity_units())
+        .map(|units| (units * 1000.0).round() as u64)
+        .unwrap_or_default()
+}
+
+fn phase_latency(target: &str, phase: Phase) -> Histogram {
+    Histogram::new(
+        "phase_latency",
+        vec![
+            ("target", target.to_string()),
+            ("phase", phase.as_str().to_string()),
+        ],
+    )
+}
+
 fn configure_metrics(args: &Args) -> Metrics {
     fn make_metrics(args: &Args) -> Metrics {
-        let factory = goodmetrics::default_gauge_factory();
+        let target = args.scenario.clone().unwrap_or_else(|| "unset".to_string());
         Metrics {
-            latency: factory.dimensioned_gauge_histogram(
-                "ddb_load_tester",
-                "latency",
-                GaugeDimensions::new([(
-                    "target",
-                    args.scenario
-                        .clone()
-                        .unwrap_or_else(|| "unset".to_string())
-                        .to_string(),
-                )]),
+            operations: Operation::ALL
+                .iter()
+                .map(|operation| OperationMetrics::new(&target, *operation))
+                .collect(),
+            serialization_latency: phase_latency(&target, Phase::Serialization),
+            signing_latency: phase_latency(&target, Phase::Signing),
+            transmit_latency: phase_latency(&target, Phase::Transmit),
+            deserialization_latency: phase_latency(&target, Phase::Deserialization),
+            retries: Counter::new("retries", vec![("target", target.clone())]),
+            read_capacity: Counter::new(
+                "consumed_read_capacity_milliunits",
+                vec![("target", target.clone())],
+            ),
+            write_capacity: Counter::new(
+                "consumed_write_capacity_milliunits",
+                vec![("target", target.clone())],
             ),
+            in_flight: Gauge::new("in_flight", vec![("target", target.clone())]),
+            read_modify_write: Arc::new(ReadModifyWriteMetrics::new(&target)),
+            query_pagination: PaginationMetrics::new(&target, Operation::Query),
+            scan_pagination: PaginationMetrics::new(&target, Operation::Scan),
+            batch_get: BatchMetrics::new(&target, Operation::BatchGetItem),
+            batch_write: BatchMetrics::new(&target, Operation::BatchWriteItem),
+            target,
         }
     }
 
@@ -47,7 +547,7 @@ fn configure_metrics(args: &Args) -> Metrics {
     } else if let Some(_metrics_authorization) = &args.metrics_authorization {
         panic!("you must set --metrics-endpoint if you set --metrics-authorization");
     } else {
-        log::info!("not using metrics");
+        log::info!("not pushing metrics to opentelemetry");
         // they won't do anything
         return make_metrics(args);
     };
@@ -0,0 +1,170 @@
+use std::{fmt::Display, str::FromStr, sync::Arc};
+
+use rand::Rng;
+
+use crate::{
+    args::Args,
+    batches::BatchOptions,
+    pagination::{QueryOptions, ScanOptions},
+    partiql::PartiqlOptions,
+    read_modify_write::ReadModifyWriteOptions,
+    transactions::TransactionOptions,
+    ttl::Ttl,
+};
+
+/// What a worker does with one tick of its rate limiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixOperation {
+    /// GetItem, then PutItem to populate the item if it was missing
+    Get,
+    /// Unconditional PutItem
+    Put,
+    /// GetItem and a conditional UpdateItem on the item's version, retried when it loses the race
+    ReadModifyWrite,
+    /// TransactGetItems of --transaction-items items
+    TransactGet,
+    /// TransactWriteItems putting --transaction-items items
+    TransactWrite,
+    /// Query of a partition, following up to --query-max-pages pages
+    Query,
+    /// Parallel Scan of --scan-segments segments
+    Scan,
+    /// BatchGetItem of --batch-get-items items
+    BatchGet,
+    /// BatchWriteItem putting --batch-write-items items
+    BatchWrite,
+    /// PartiQL SELECT of one item
+    PartiqlSelect,
+    /// PartiQL INSERT of one item
+    PartiqlInsert,
+    /// PartiQL UPDATE of one item
+    PartiqlUpdate,
+    /// BatchExecuteStatement of --partiql-batch-statements SELECTs
+    PartiqlBatch,
+}
+impl MixOperation {
+    const ALL: [MixOperation; 13] = [
+        MixOperation::Get,
+        MixOperation::Put,
+        MixOperation::ReadModifyWrite,
+        MixOperation::TransactGet,
+        MixOperation::TransactWrite,
+        MixOperation::Query,
+        MixOperation::Scan,
+        MixOperation::BatchGet,
+        MixOperation::BatchWrite,
+        MixOperation::PartiqlSelect,
+        MixOperation::PartiqlInsert,
+        MixOperation::PartiqlUpdate,
+        MixOperation::PartiqlBatch,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MixOperation::Get => "get",
+            MixOperation::Put => "put",
+            MixOperation::ReadModifyWrite => "rmw",
+            MixOperation::TransactGet => "tget",
+            MixOperation::TransactWrite => "twrite",
+            MixOperation::Query => "query",
+            MixOperation::Scan => "scan",
+            MixOperation::BatchGet => "bget",
+            MixOperation::BatchWrite => "bwrite",
+            MixOperation::PartiqlSelect => "pselect",
+            MixOperation::PartiqlInsert => "pinsert",
+            MixOperation::PartiqlUpdate => "pupdate",
+            MixOperation::PartiqlBatch => "pbatch",
+        }
+    }
+}
+
+/// Settings for the operations in the mix that have any.
+#[derive(Debug, Clone)]
+pub struct OperationOptions {
+    pub read_modify_write: ReadModifyWriteOptions,
+    pub transaction: TransactionOptions,
+    pub query: Arc<QueryOptions>,
+    pub scan: ScanOptions,
+    pub batch: BatchOptions,
+    pub partiql: PartiqlOptions,
+    pub ttl: Option<Ttl>,
+}
+impl OperationOptions {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            read_modify_write: ReadModifyWriteOptions::new(args),
+            transaction: TransactionOptions::new(args),
+            query: Arc::new(QueryOptions::new(args)),
+            scan: ScanOptions::new(args),
+            batch: BatchOptions::new(args),
+            partiql: PartiqlOptions::new(args),
+            ttl: Ttl::new(args),
+        }
+    }
+}
+
+/// Weighted operations, like `get=90,put=10`. A bare name has weight 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationMix {
+    weights: Vec<(MixOperation, u32)>,
+    total: u32,
+}
+impl OperationMix {
+    pub fn choose(&self, random: &mut impl Rng) -> MixOperation {
+        let mut choice = random.random_range(0..self.total);
+        for (operation, weight) in &self.weights {
+            if choice < *weight {
+                return *operation;
+            }
+            choice -= weight;
+        }
+        unreachable!("the choice is less than the total weight")
+    }
+}
+impl FromStr for OperationMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Vec::new();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (name, weight) = entry.split_once('=').unwrap_or((entry, "1"));
+            let operation = MixOperation::ALL
+                .into_iter()
+                .find(|operation| operation.as_str() == name.trim())
+                .ok_or_else(|| {
+                    format!(
+                        "unknown operation {name}, expected one of {}",
+                        MixOperation::ALL
+                            .map(|operation| operation.as_str())
+                            .join(", ")
+                    )
+                })?;
+            let weight: u32 = weight
+                .trim()
+                .parse()
+                .map_err(|e| format!("bad weight for {name}: {e}"))?;
+            if 0 < weight {
+                weights.push((operation, weight));
+            }
+        }
+        let total = weights.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return Err("the mix needs at least one operation with a weight above 0".to_string());
+        }
+        Ok(Self { weights, total })
+    }
+}
+impl Display for OperationMix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries: Vec<String> = self
+            .weights
+            .iter()
+            .map(|(operation, weight)| format!("{}={weight}", operation.as_str()))
+            .collect();
+        f.write_str(&entries.join(","))
+    }
+}
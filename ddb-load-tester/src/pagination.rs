@@ -0,0 +1,182 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnConsumedCapacity};
+use tokio::task::JoinSet;
+
+use crate::{
+    args::Args,
+    cache_status_interceptor::CacheStatus,
+    metrics::{Metrics, Operation},
+};
+
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    pub table: String,
+    pub partition_key: String,
+    /// Sort key name and the inclusive range of string values to query
+    pub sort_range: Option<(String, String, String)>,
+    pub limit: Option<i32>,
+    pub forward: bool,
+    pub max_pages: u32,
+}
+impl QueryOptions {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            table: args.query_table.clone(),
+            partition_key: args.query_partition_key.clone(),
+            sort_range: args.query_sort_key.clone().map(|sort_key| {
+                (
+                    sort_key,
+                    args.query_sort_from.clone().expect("clap requires it"),
+                    args.query_sort_to.clone().expect("clap requires it"),
+                )
+            }),
+            limit: args.query_limit,
+            forward: !args.query_backward,
+            max_pages: args.query_max_pages,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub table: String,
+    pub segments: i32,
+    pub limit: Option<i32>,
+    pub max_pages: u32,
+}
+impl ScanOptions {
+    pub fn new(args: &Args) -> Self {
+        assert!(0 < args.scan_segments, "--scan-segments must be above 0");
+        Self {
+            table: args.scan_table.clone(),
+            segments: args.scan_segments,
+            limit: args.scan_limit,
+            max_pages: args.scan_max_pages,
+        }
+    }
+}
+
+/// Query a partition, following `LastEvaluatedKey` for up to `max_pages` pages.
+/// Each page is recorded as a Query, and the whole query as a paginated Query.
+pub async fn query(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    partition: AttributeValue,
+    options: Arc<QueryOptions>,
+) {
+    let _in_flight = metrics.start_request();
+    let start = Instant::now();
+    let mut request = client
+        .query()
+        .table_name(&options.table)
+        .expression_attribute_names("#partition", &options.partition_key)
+        .expression_attribute_values(":partition", partition)
+        .scan_index_forward(options.forward)
+        .set_limit(options.limit)
+        .return_consumed_capacity(ReturnConsumedCapacity::Total);
+    request = match &options.sort_range {
+        Some((sort_key, from, to)) => request
+            .key_condition_expression("#partition = :partition AND #sort BETWEEN :from AND :to")
+            .expression_attribute_names("#sort", sort_key)
+            .expression_attribute_values(":from", AttributeValue::S(from.clone()))
+            .expression_attribute_values(":to", AttributeValue::S(to.clone())),
+        None => request.key_condition_expression("#partition = :partition"),
+    };
+
+    let mut pages = 0;
+    let mut start_key: Option<HashMap<String, AttributeValue>> = None;
+    while pages < options.max_pages {
+        let page_start = Instant::now();
+        let output = match request
+            .clone()
+            .set_exclusive_start_key(start_key.take())
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("failed to query: {e:#?}");
+                metrics.record_error(Operation::Query, &e);
+                return;
+            }
+        };
+        metrics.record_latency(Operation::Query, page_start.elapsed(), CacheStatus::Absent);
+        metrics.record_read_capacity(output.consumed_capacity());
+        pages += 1;
+        match output.last_evaluated_key {
+            Some(key) => start_key = Some(key),
+            None => break,
+        }
+    }
+    metrics.record_pagination(Operation::Query, pages, start.elapsed());
+}
+
+/// Scan `segments` segments of the table in parallel, each for up to `max_pages` pages.
+/// Each page is recorded as a Scan, and the whole scan as a paginated Scan.
+pub async fn scan(client: aws_sdk_dynamodb::Client, metrics: Metrics, options: ScanOptions) {
+    let _in_flight = metrics.start_request();
+    let start = Instant::now();
+    let mut segments = JoinSet::new();
+    for segment in 0..options.segments {
+        segments.spawn(scan_segment(
+            client.clone(),
+            metrics.clone(),
+            segment,
+            options.clone(),
+        ));
+    }
+    let mut pages = 0;
+    let mut failed = false;
+    while let Some(segment) = segments.join_next().await {
+        match segment.expect("scan segments must not panic") {
+            Some(segment_pages) => pages += segment_pages,
+            None => failed = true,
+        }
+    }
+    if !failed {
+        metrics.record_pagination(Operation::Scan, pages, start.elapsed());
+    }
+}
+
+/// The number of pages, or None if a page failed.
+async fn scan_segment(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    segment: i32,
+    options: ScanOptions,
+) -> Option<u32> {
+    let request = client
+        .scan()
+        .table_name(options.table)
+        .segment(segment)
+        .total_segments(options.segments)
+        .set_limit(options.limit)
+        .return_consumed_capacity(ReturnConsumedCapacity::Total);
+    let mut pages = 0;
+    let mut start_key = None;
+    while pages < options.max_pages {
+        let page_start = Instant::now();
+        let output = match request
+            .clone()
+            .set_exclusive_start_key(start_key.take())
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("failed to scan segment {segment}: {e:#?}");
+                metrics.record_error(Operation::Scan, &e);
+                return None;
+            }
+        };
+        metrics.record_latency(Operation::Scan, page_start.elapsed(), CacheStatus::Absent);
+        metrics.record_read_capacity(output.consumed_capacity());
+        pages += 1;
+        match output.last_evaluated_key {
+            Some(key) => start_key = Some(key),
+            None => break,
+        }
+    }
+    Some(pages)
+}
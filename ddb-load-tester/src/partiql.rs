@@ -0,0 +1,170 @@
+use std::{borrow::Cow, time::Instant};
+
+use aws_sdk_dynamodb::types::{AttributeValue, BatchStatementRequest, ReturnConsumedCapacity};
+
+use crate::{
+    args::Args,
+    cache_status_interceptor::{CacheStatus, CacheStatusInterceptor},
+    metrics::{Metrics, Operation},
+    ttl::Ttl,
+};
+
+/// DynamoDB's limit on statements per BatchExecuteStatement
+const MAX_BATCH_STATEMENTS: usize = 25;
+
+const SELECT: &str = r#"SELECT * FROM "users" WHERE "user" = ?"#;
+const INSERT: &str = r#"INSERT INTO "users" VALUE {'user': ?, 'value': ?}"#;
+const UPDATE: &str = r#"UPDATE "users" SET "value" = ? WHERE "user" = ?"#;
+/// INSERT with a TTL attribute, whose value is the last parameter.
+fn insert_with_ttl(attribute: &str) -> String {
+    format!(
+        r#"INSERT INTO "users" VALUE {{'user': ?, 'value': ?, '{}': ?}}"#,
+        attribute.replace('\'', "''")
+    )
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PartiqlOptions {
+    pub batch_statements: usize,
+    pub consistent_read: bool,
+}
+impl PartiqlOptions {
+    pub fn new(args: &Args) -> Self {
+        assert!(
+            (1..=MAX_BATCH_STATEMENTS).contains(&args.partiql_batch_statements),
+            "--partiql-batch-statements must be from 1 to {MAX_BATCH_STATEMENTS}"
+        );
+        Self {
+            batch_statements: args.partiql_batch_statements,
+            consistent_read: args.partiql_consistent_read,
+        }
+    }
+}
+
+/// The PartiQL statements the tester sends, each the equivalent of an item api call.
+#[derive(Debug, Clone, Copy)]
+pub enum Statement {
+    /// Like GetItem
+    Select,
+    /// Like a PutItem that fails with `DuplicateItemException` if the item exists
+    Insert,
+    /// Like an UpdateItem that fails with `ConditionalCheckFailedException` if the item doesn't exist
+    Update,
+}
+impl Statement {
+    fn parameters(self, user_id: AttributeValue) -> Vec<AttributeValue> {
+        let value = AttributeValue::S("test".to_string());
+        match self {
+            Statement::Select => vec![user_id],
+            Statement::Insert => vec![user_id, value],
+            Statement::Update => vec![value, user_id],
+        }
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            Statement::Select => SELECT,
+            Statement::Insert => INSERT,
+            Statement::Update => UPDATE,
+        }
+    }
+}
+
+/// ExecuteStatement for one user, recorded with the cache status like GetItem so the two can be
+/// compared. Inserted items get the TTL attribute.
+pub async fn execute_statement(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    statement: Statement,
+    user_id: AttributeValue,
+    options: PartiqlOptions,
+    ttl: Option<Ttl>,
+) {
+    let _in_flight = metrics.start_request();
+    let cache_status = CacheStatusInterceptor::default();
+    let mut text = Cow::Borrowed(statement.text());
+    let mut parameters = statement.parameters(user_id);
+    if let (Statement::Insert, Some(ttl)) = (statement, &ttl) {
+        let (attribute, expires) = ttl.expiry();
+        text = Cow::Owned(insert_with_ttl(&attribute));
+        parameters.push(expires);
+    }
+    let mut request = client
+        .execute_statement()
+        .statement(text)
+        .set_parameters(Some(parameters))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total);
+    if let Statement::Select = statement {
+        request = request.consistent_read(options.consistent_read);
+    }
+    let start = Instant::now();
+    match request
+        .customize()
+        .interceptor(cache_status.clone())
+        .send()
+        .await
+    {
+        Ok(output) => {
+            let cache_status = cache_status.status();
+            metrics.record_latency(Operation::ExecuteStatement, start.elapsed(), cache_status);
+            match (statement, cache_status) {
+                (_, CacheStatus::Hit) => (),
+                (Statement::Select, _) => metrics.record_read_capacity(output.consumed_capacity()),
+                _ => metrics.record_write_capacity(output.consumed_capacity()),
+            }
+        }
+        Err(e) => {
+            log::debug!("failed to execute statement: {e:#?}");
+            metrics.record_error(Operation::ExecuteStatement, &e);
+        }
+    }
+}
+
+/// BatchExecuteStatement a SELECT for each user. Statements fail one by one, so each failure is
+/// counted by its code in the `statement_errors` metric.
+pub async fn batch_select(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    user_ids: Vec<AttributeValue>,
+    options: PartiqlOptions,
+) {
+    let _in_flight = metrics.start_request();
+    let statements = user_ids
+        .into_iter()
+        .map(|user_id| {
+            BatchStatementRequest::builder()
+                .statement(SELECT)
+                .set_parameters(Some(Statement::Select.parameters(user_id)))
+                .consistent_read(options.consistent_read)
+                .build()
+                .expect("must be a complete statement")
+        })
+        .collect();
+    let start = Instant::now();
+    match client
+        .batch_execute_statement()
+        .set_statements(Some(statements))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total)
+        .send()
+        .await
+    {
+        Ok(output) => {
+            metrics.record_latency(
+                Operation::BatchExecuteStatement,
+                start.elapsed(),
+                CacheStatus::Absent,
+            );
+            for capacity in output.consumed_capacity() {
+                metrics.record_read_capacity(Some(capacity));
+            }
+            for error in output.responses().iter().filter_map(|r| r.error()) {
+                let code = error.code().map_or("unknown", |code| code.as_str());
+                metrics.record_statement_error(Operation::BatchExecuteStatement, code);
+            }
+        }
+        Err(e) => {
+            log::error!("failed to batch execute statements: {e:#?}");
+            metrics.record_error(Operation::BatchExecuteStatement, &e);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::{sync::Arc, time::Instant};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::{load_generator_task::put_user, ttl::Ttl};
+
+/// Write each key once before the run, outside of the run's metrics.
+pub async fn populate(
+    client: aws_sdk_dynamodb::Client,
+    keys: &[String],
+    concurrency: usize,
+    ttl: Option<Ttl>,
+) {
+    log::info!("populating {} items", keys.len());
+    let started = Instant::now();
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut writes = JoinSet::new();
+    let (mut written, mut failed) = (0, 0);
+    let mut count = |result: Result<Result<_, _>, _>| match result.expect("writes must not panic") {
+        Ok(_) => written += 1,
+        Err(e) => {
+            log::warn!("failed to populate an item: {e}");
+            failed += 1;
+        }
+    };
+    for key in keys {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        let request = put_user(&client, AttributeValue::S(key.clone()), ttl.as_ref());
+        writes.spawn(async move {
+            let _permit = permit;
+            request.send().await
+        });
+        while let Some(result) = writes.try_join_next() {
+            count(result);
+        }
+    }
+    while let Some(result) = writes.join_next().await {
+        count(result);
+    }
+    log::info!(
+        "populated {written} items in {:.1}s, {failed} failed",
+        started.elapsed().as_secs_f64()
+    );
+}
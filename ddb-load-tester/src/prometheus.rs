@@ -0,0 +1,200 @@
+use std::{fmt::Write, sync::atomic::Ordering};
+
+use axum::{Router, http::header, response::IntoResponse, routing::get};
+
+use crate::local_metrics::{self, BUCKET_COUNT, Series, SeriesKey};
+
+/// Only every 8th bucket (each power of 2 microseconds) is exposed, to keep scrapes small.
+const EXPOSED_BUCKET_STRIDE: usize = 8;
+
+/// Serve the local registry at `/metrics` in the prometheus text exposition format.
+pub async fn serve(listener: tokio::net::TcpListener) {
+    log::info!(
+        "serving prometheus metrics on http://{}/metrics",
+        listener.local_addr().expect("must have an address")
+    );
+    let app = Router::new().route("/metrics", get(scrape));
+    axum::serve(listener, app)
+        .await
+        .expect("prometheus endpoint must not fail");
+}
+
+async fn scrape() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&local_metrics::default_registry().series()),
+    )
+}
+
+fn render(series: &[(SeriesKey, Series)]) -> String {
+    let mut out = String::new();
+    let mut previous_family = None;
+    for (key, series) in series {
+        let (family, kind) = match series {
+            Series::Counter(_) => (format!("ddb_load_tester_{}_total", key.name), "counter"),
+            Series::Gauge(_) => (format!("ddb_load_tester_{}", key.name), "gauge"),
+            Series::Histogram(_) => (format!("ddb_load_tester_{}_seconds", key.name), "histogram"),
+        };
+        if previous_family.as_ref() != Some(&family) {
+            let _ = writeln!(out, "# TYPE {family} {kind}");
+        }
+        match series {
+            Series::Counter(counter) => {
+                let _ = writeln!(
+                    out,
+                    "{family}{} {}",
+                    labels(&key.labels, None),
+                    counter.load(Ordering::Relaxed)
+                );
+            }
+            Series::Gauge(gauge) => {
+                let _ = writeln!(
+                    out,
+                    "{family}{} {}",
+                    labels(&key.labels, None),
+                    gauge.load(Ordering::Relaxed)
+                );
+            }
+            Series::Histogram(histogram) => {
+                let snapshot = histogram.snapshot();
+                let mut cumulative = 0;
+                for (index, count) in snapshot.counts.iter().enumerate() {
+                    cumulative += count;
+                    if index % EXPOSED_BUCKET_STRIDE == 0 && index != BUCKET_COUNT - 1 {
+                        let le = local_metrics::bucket_upper_bound(index) as f64 / 1e9;
+                        let _ = writeln!(
+                            out,
+                            "{family}_bucket{} {cumulative}",
+                            labels(&key.labels, Some(&le.to_string()))
+                        );
+                    }
+                }
+                let _ = writeln!(
+                    out,
+                    "{family}_bucket{} {cumulative}",
+                    labels(&key.labels, Some("+Inf"))
+                );
+                let _ = writeln!(
+                    out,
+                    "{family}_sum{} {}",
+                    labels(&key.labels, None),
+                    snapshot.sum_nanos as f64 / 1e9
+                );
+                let _ = writeln!(
+                    out,
+                    "{family}_count{} {cumulative}",
+                    labels(&key.labels, None)
+                );
+            }
+        }
+        previous_family = Some(family);
+    }
+    out
+}
+
+fn labels(labels: &[(&'static str, String)], le: Option<&str>) -> String {
+    let mut rendered: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", escape(value)))
+        .collect();
+    if let Some(le) = le {
+        rendered.push(format!("le=\"{le}\""));
+    }
+    if rendered.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", rendered.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::render;
+    use crate::local_metrics::Registry;
+
+    #[test]
+    fn counters_and_gauges_render_one_type_line_per_family() {
+        let registry = Registry::default();
+        registry
+            .counter("requests", vec![("operation", "get".to_string())])
+            .fetch_add(3, Ordering::Relaxed);
+        registry
+            .counter("requests", vec![("operation", "put".to_string())])
+            .fetch_add(1, Ordering::Relaxed);
+        registry
+            .gauge("in_flight", vec![])
+            .fetch_sub(2, Ordering::Relaxed);
+        registry.counter(
+            "errors",
+            vec![("message", "a \"quoted\" \\ line\nbreak".to_string())],
+        );
+        assert_eq!(
+            render(&registry.series()),
+            [
+                "# TYPE ddb_load_tester_errors_total counter",
+                r#"ddb_load_tester_errors_total{message="a \"quoted\" \\ line\nbreak"} 0"#,
+                "# TYPE ddb_load_tester_in_flight gauge",
+                "ddb_load_tester_in_flight -2",
+                "# TYPE ddb_load_tester_requests_total counter",
+                r#"ddb_load_tester_requests_total{operation="get"} 3"#,
+                r#"ddb_load_tester_requests_total{operation="put"} 1"#,
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn histograms_render_cumulative_buckets_in_seconds() {
+        let registry = Registry::default();
+        let histogram = registry.histogram("latency", vec![("operation", "get".to_string())]);
+        histogram.observe(1_500);
+        histogram.observe(3_000_000_000);
+        let rendered = render(&registry.series());
+        let lines: Vec<_> = rendered.lines().collect();
+
+        assert_eq!(lines[0], "# TYPE ddb_load_tester_latency_seconds histogram");
+        let buckets: Vec<(f64, u64)> = lines
+            .iter()
+            .filter_map(|line| {
+                line.strip_prefix(r#"ddb_load_tester_latency_seconds_bucket{operation="get",le=""#)
+            })
+            .map(|rest| {
+                let (le, count) = rest.split_once("\"} ").expect("buckets have a count");
+                (
+                    if le == "+Inf" {
+                        f64::INFINITY
+                    } else {
+                        le.parse().expect("le is a number")
+                    },
+                    count.parse().expect("counts are integers"),
+                )
+            })
+            .collect();
+        assert_eq!(buckets[0], (0.000001, 0));
+        assert_eq!(buckets[1], (0.000002, 1));
+        assert!(
+            buckets
+                .windows(2)
+                .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 <= pair[1].1)
+        );
+        assert!(buckets.contains(&(4.194304, 2)));
+        assert_eq!(buckets.last(), Some(&(f64::INFINITY, 2)));
+        assert_eq!(
+            &lines[lines.len() - 2..],
+            [
+                r#"ddb_load_tester_latency_seconds_sum{operation="get"} 3.0000015"#,
+                r#"ddb_load_tester_latency_seconds_count{operation="get"} 2"#,
+            ]
+        );
+    }
+}
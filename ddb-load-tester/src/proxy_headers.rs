@@ -0,0 +1,252 @@
+use std::{borrow::Cow, path::PathBuf, str::FromStr};
+
+use crate::args::Args;
+
+/// Where the value of the proxy's auth header comes from. It is read once, when the run starts.
+///
+/// * `env:MOMENTO_AUTH_TOKEN` reads an environment variable
+/// * `file:/run/secrets/token` reads a file
+/// * `command:vault read -field=token secret/proxy` runs a shell command and uses what it prints
+/// * `none` sends no auth header
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthSource {
+    Env(String),
+    File(PathBuf),
+    Command(String),
+    None,
+}
+impl AuthSource {
+    /// The auth header value, without surrounding whitespace, or none to send no auth header.
+    pub fn resolve(&self) -> Option<String> {
+        let value = match self {
+            AuthSource::Env(name) => std::env::var(name)
+                .unwrap_or_else(|_| panic!("must set {name} for the proxy auth header")),
+            AuthSource::File(path) => std::fs::read_to_string(path).unwrap_or_else(|e| {
+                panic!(
+                    "must be able to read {} for the proxy auth header: {e}",
+                    path.display()
+                )
+            }),
+            AuthSource::Command(command) => {
+                let output = std::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .expect("must be able to run the proxy auth command");
+                assert!(
+                    output.status.success(),
+                    "the proxy auth command failed with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+                String::from_utf8(output.stdout).expect("the proxy auth command must print text")
+            }
+            AuthSource::None => return None,
+        };
+        Some(value.trim().to_string())
+    }
+}
+impl FromStr for AuthSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "none" {
+            return Ok(AuthSource::None);
+        }
+        match s.split_once(':') {
+            Some(("env", name)) if !name.is_empty() => Ok(AuthSource::Env(name.to_string())),
+            Some(("file", path)) if !path.is_empty() => Ok(AuthSource::File(path.into())),
+            Some(("command", command)) if !command.trim().is_empty() => {
+                Ok(AuthSource::Command(command.to_string()))
+            }
+            _ => Err(format!(
+                "bad auth source {s}, expected env:NAME, file:PATH, command:COMMAND or none"
+            )),
+        }
+    }
+}
+
+/// A static header for the proxy, like `x-api-key: abc`. The value may contain colons.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxyHeader {
+    pub name: String,
+    pub value: String,
+}
+impl FromStr for ProxyHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("bad header {s}, expected name: value"))?;
+        let name = header_name(name.trim())?;
+        let value = value.trim();
+        http::HeaderValue::from_str(value).map_err(|e| format!("bad value for {name}: {e}"))?;
+        Ok(Self {
+            name,
+            value: value.to_string(),
+        })
+    }
+}
+
+/// Check a header name from the command line, and lowercase it like http does.
+pub fn header_name(name: &str) -> Result<String, String> {
+    http::HeaderName::from_str(name)
+        .map(|name| name.to_string())
+        .map_err(|e| format!("bad header name {name}: {e}"))
+}
+
+/// The headers `ProxyInterceptor` adds for the proxy: the auth header, then the static ones.
+pub fn proxy_headers(args: &Args) -> Vec<(Cow<'static, str>, Cow<'static, str>)> {
+    args.proxy_auth
+        .resolve()
+        .map(|value| {
+            // Checked here, so a bad value stops the run before the first request instead of
+            // failing every one. The value is a secret, so it isn't in the message.
+            if let Err(e) = http::HeaderValue::from_str(&value) {
+                panic!(
+                    "the proxy auth value from {:?} must be a valid {} header value: {e}",
+                    args.proxy_auth, args.proxy_auth_header
+                );
+            }
+            (args.proxy_auth_header.clone().into(), value.into())
+        })
+        .into_iter()
+        .chain(
+            args.proxy_header
+                .iter()
+                .map(|header| (header.name.clone().into(), header.value.clone().into())),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::Parser;
+
+    use super::{AuthSource, ProxyHeader, header_name, proxy_headers};
+    use crate::args::Args;
+
+    #[test]
+    fn auth_sources_parse() {
+        let parse = |s: &str| s.parse::<AuthSource>();
+        assert_eq!(parse("env:TOKEN"), Ok(AuthSource::Env("TOKEN".to_string())));
+        assert_eq!(
+            parse("file:/run/secrets/token"),
+            Ok(AuthSource::File(PathBuf::from("/run/secrets/token")))
+        );
+        assert_eq!(
+            parse("command:vault read -field=token secret/proxy"),
+            Ok(AuthSource::Command(
+                "vault read -field=token secret/proxy".to_string()
+            ))
+        );
+        assert_eq!(parse("none"), Ok(AuthSource::None));
+        // Only the first colon separates the kind
+        assert_eq!(
+            parse("file:C:/token"),
+            Ok(AuthSource::File(PathBuf::from("C:/token")))
+        );
+        assert_eq!(
+            parse("command:echo a:b"),
+            Ok(AuthSource::Command("echo a:b".to_string()))
+        );
+    }
+
+    #[test]
+    fn bad_auth_sources_are_rejected() {
+        for bad in [
+            "",
+            "TOKEN",
+            "env:",
+            "file:",
+            "command:",
+            "command:  ",
+            "vault:x",
+            "None",
+            "none:",
+        ] {
+            assert!(bad.parse::<AuthSource>().is_err(), "{bad:?} parsed");
+        }
+    }
+
+    #[test]
+    fn headers_parse() {
+        let parse = |s: &str| s.parse::<ProxyHeader>();
+        assert_eq!(
+            parse("X-Api-Key: abc"),
+            Ok(ProxyHeader {
+                name: "x-api-key".to_string(),
+                value: "abc".to_string(),
+            })
+        );
+        // The value keeps its colons
+        assert_eq!(
+            parse("x-forwarded-for:http://10.0.0.1:8080"),
+            Ok(ProxyHeader {
+                name: "x-forwarded-for".to_string(),
+                value: "http://10.0.0.1:8080".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("x-empty:"),
+            Ok(ProxyHeader {
+                name: "x-empty".to_string(),
+                value: String::new(),
+            })
+        );
+        for bad in ["x-api-key", ": abc", "bad name: abc", "x-api-key: a\nb"] {
+            assert!(parse(bad).is_err(), "{bad:?} parsed");
+        }
+    }
+
+    #[test]
+    fn header_names_are_lowercased_and_checked() {
+        assert_eq!(
+            header_name("X-Momento-Authorization"),
+            Ok("x-momento-authorization".to_string())
+        );
+        for bad in ["", "bad name", "bad:name", "bad\nname", "bäd"] {
+            assert!(header_name(bad).is_err(), "{bad:?} is a header name");
+        }
+    }
+
+    #[test]
+    fn proxy_headers_are_the_auth_header_then_the_static_ones() {
+        let args = Args::parse_from([
+            "ddb-load-tester",
+            "--proxy-auth-header",
+            "X-Auth",
+            "--proxy-auth",
+            "command:echo '  token  '",
+            "--proxy-header",
+            "x-a: 1",
+            "--proxy-header",
+            "x-b: 2",
+        ]);
+        let headers: Vec<_> = proxy_headers(&args)
+            .into_iter()
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                ("x-auth".to_string(), "token".to_string()),
+                ("x-a".to_string(), "1".to_string()),
+                ("x-b".to_string(), "2".to_string()),
+            ]
+        );
+
+        let args = Args::parse_from(["ddb-load-tester", "--proxy-auth", "none"]);
+        assert!(proxy_headers(&args).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "must be a valid x-momento-authorization header value")]
+    fn auth_values_must_be_header_values() {
+        let args = Args::parse_from(["ddb-load-tester", "--proxy-auth", "command:printf 'a\\nb'"]);
+        proxy_headers(&args);
+    }
+}
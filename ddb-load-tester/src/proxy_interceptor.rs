@@ -1,23 +1,24 @@
 use std::borrow::Cow;
 
 /// I have to modify the request uri after it is signed. It is the Function's job to replace
-/// the request uri with x-uri.
+/// the request uri with the target header.
 #[derive(Debug)]
 pub struct ProxyInterceptor {
     proxy_uri: String,
-    auth_header_name: Cow<'static, str>,
-    auth_header_value: Cow<'static, str>,
+    target_header_name: Cow<'static, str>,
+    /// Auth and static headers for the proxy
+    headers: Vec<(Cow<'static, str>, Cow<'static, str>)>,
 }
 impl ProxyInterceptor {
     pub fn new(
         value: String,
-        auth_header_name: Cow<'static, str>,
-        auth_header_value: Cow<'static, str>,
+        target_header_name: Cow<'static, str>,
+        headers: Vec<(Cow<'static, str>, Cow<'static, str>)>,
     ) -> Self {
         Self {
             proxy_uri: value,
-            auth_header_name,
-            auth_header_value,
+            target_header_name,
+            headers,
         }
     }
 }
@@ -44,18 +45,126 @@ impl aws_sdk_dynamodb::config::Intercept for ProxyInterceptor {
             .try_into()
             .expect("must be a valid uri");
 
-        // Proxy uses x-uri to replace the original uri when it needs to forward the request
-        context
-            .request_mut()
-            .headers_mut()
-            .insert("x-uri", requested);
+        // Proxy uses the target header to replace the original uri when it needs to forward the request
+        let headers = context.request_mut().headers_mut();
+        headers.insert(self.target_header_name.clone(), requested);
 
-        // Include the auth header for the proxy
-        context.request_mut().headers_mut().insert(
-            self.auth_header_name.clone(),
-            self.auth_header_value.clone(),
-        );
+        // Include the auth and static headers for the proxy
+        for (name, value) in &self.headers {
+            headers.append(name.clone(), value.clone());
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ProxyInterceptor;
+    use crate::test_support::{self, CaptureServer, DYNAMODB_ENDPOINT, Forwarder, SignedHeaders};
+
+    #[tokio::test]
+    async fn forwards_the_signed_request_to_the_proxy() {
+        let server = CaptureServer::start().await;
+        let signed = SignedHeaders::default();
+        let client = test_support::client(
+            ProxyInterceptor::new(
+                format!("{}/proxy", server.url),
+                "x-uri".into(),
+                vec![("x-momento-authorization".into(), "token".into())],
+            ),
+            signed.clone(),
+        );
+        test_support::get_item(&client).await;
+
+        let request = server.request();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.uri.path(), "/proxy");
+        assert_eq!(
+            request.header("x-uri"),
+            Some(format!("{DYNAMODB_ENDPOINT}/").as_str())
+        );
+        assert_eq!(request.header("x-momento-authorization"), Some("token"));
+
+        // The proxy must be able to forward the request exactly as it was signed
+        let headers = signed.get();
+        assert!(headers.iter().any(|(name, _)| name == "authorization"));
+        for (name, value) in &headers {
+            assert_eq!(request.header(name), Some(value.as_str()), "header {name}");
+        }
+        for name in signed.signed_names() {
+            assert!(
+                name == "host" || request.headers.contains_key(&name),
+                "signed header {name} must be sent"
+            );
+        }
+        assert!(request.body.starts_with(b"{"));
+    }
+
+    #[tokio::test]
+    async fn sends_configured_headers() {
+        let server = CaptureServer::start().await;
+        let client = test_support::client(
+            ProxyInterceptor::new(
+                server.url.clone(),
+                "x-forward-to".into(),
+                vec![
+                    ("authorization-proxy".into(), "Bearer abc".into()),
+                    ("x-tenant".into(), "a".into()),
+                    ("x-tenant".into(), "b".into()),
+                ],
+            ),
+            SignedHeaders::default(),
+        );
+        test_support::get_item(&client).await;
+
+        let request = server.request();
+        assert_eq!(
+            request.header("x-forward-to"),
+            Some(format!("{DYNAMODB_ENDPOINT}/").as_str())
+        );
+        assert_eq!(request.header("x-uri"), None);
+        assert_eq!(request.header("x-momento-authorization"), None);
+        assert_eq!(request.header("authorization-proxy"), Some("Bearer abc"));
+        let tenants: Vec<_> = request.headers.get_all("x-tenant").iter().collect();
+        assert_eq!(tenants, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn forwarded_requests_pass_signature_checks() {
+        fn restore(received: Vec<(String, String)>) -> (String, Vec<(String, String)>) {
+            let target_uri = received
+                .iter()
+                .find(|(name, _)| name == "x-uri")
+                .map(|(_, value)| value.clone())
+                .expect("the proxy gets the target uri");
+            let host = target_uri
+                .parse::<http::Uri>()
+                .expect("the target must be a uri")
+                .host()
+                .expect("the target has a host")
+                .to_string();
+            let mut headers: Vec<_> = received
+                .into_iter()
+                .filter(|(name, _)| {
+                    !["x-uri", "x-momento-authorization", "host"].contains(&name.as_str())
+                })
+                .collect();
+            headers.push(("host".to_string(), host));
+            (target_uri, headers)
+        }
+
+        let forwarder = Forwarder::start(restore).await;
+        let client = test_support::client(
+            ProxyInterceptor::new(
+                forwarder.url.clone(),
+                "x-uri".into(),
+                vec![("x-momento-authorization".into(), "token".into())],
+            ),
+            SignedHeaders::default(),
+        );
+        test_support::put_and_get(&client)
+            .await
+            .expect("a forwarded request must pass signature checks");
+    }
+}
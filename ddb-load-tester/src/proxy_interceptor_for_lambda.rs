@@ -1,12 +1,19 @@
+use crate::args::LambdaEncoding;
+
 /// I have to modify the request uri after it is signed. It is the Function's job to replace
-/// the request uri with x-uri.
+/// the request uri with x-uri. The headers are encoded with [`ddb_proxy_protocol`], which the
+/// lambda decodes.
 #[derive(Debug)]
 pub struct ProxyInterceptorForLambda {
     proxy_uri: String,
+    encoding: LambdaEncoding,
 }
 impl ProxyInterceptorForLambda {
-    pub fn new(value: String) -> Self {
-        Self { proxy_uri: value }
+    pub fn new(value: String, encoding: LambdaEncoding) -> Self {
+        Self {
+            proxy_uri: value,
+            encoding,
+        }
     }
 }
 impl aws_sdk_dynamodb::config::Intercept for ProxyInterceptorForLambda {
@@ -30,19 +37,190 @@ impl aws_sdk_dynamodb::config::Intercept for ProxyInterceptorForLambda {
             .try_into()
             .expect("must be a valid uri");
 
-        context
-            .request_mut()
-            .headers_mut()
-            .insert("x-uri", requested);
-
         // Lambda eats sigv4 headers, so we need to rename them (then unrename in the lambda)
         let headers = context.request_mut().headers_mut();
-        let headers_clone = headers.clone();
-        for (k, _v) in headers_clone.into_iter() {
-            let v = headers.remove(k);
-            headers.insert(format!("hahaha-{k}"), v.unwrap_or_default());
+        let signed: Vec<(String, String)> = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        for (name, _) in &signed {
+            headers.remove(name.as_str());
+        }
+        let encoded = match self.encoding {
+            LambdaEncoding::Renamed => ddb_proxy_protocol::encode(&requested, signed),
+            LambdaEncoding::Envelope => {
+                vec![ddb_proxy_protocol::encode_envelope(&requested, signed)]
+            }
+        };
+        for (name, value) in encoded {
+            headers.append(name, value);
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ProxyInterceptorForLambda;
+    use crate::args::LambdaEncoding;
+    use aws_sdk_dynamodb::error::ProvideErrorMetadata;
+
+    use crate::test_support::{self, CaptureServer, DYNAMODB_ENDPOINT, Forwarder, SignedHeaders};
+
+    #[tokio::test]
+    async fn renames_the_signed_headers_for_lambda() {
+        let server = CaptureServer::start().await;
+        let signed = SignedHeaders::default();
+        let client = test_support::client(
+            ProxyInterceptorForLambda::new(
+                format!("{}/lambda", server.url),
+                LambdaEncoding::Renamed,
+            ),
+            signed.clone(),
+        );
+        test_support::get_item(&client).await;
+
+        let request = server.request();
+        assert_eq!(request.uri.path(), "/lambda");
+        assert_eq!(
+            request.header("hahaha-x-uri"),
+            Some(format!("{DYNAMODB_ENDPOINT}/").as_str())
+        );
+        assert_eq!(request.header("hahaha-x-proxy-protocol"), Some("1"));
+        assert_eq!(request.header("authorization"), None);
+        assert_eq!(request.header("x-amz-date"), None);
+
+        // Renamed headers must keep the exact bytes the signer produced
+        let headers = signed.get();
+        assert!(headers.iter().any(|(name, _)| name == "authorization"));
+        for (name, value) in &headers {
+            assert_eq!(
+                request.header(&format!("hahaha-{name}")),
+                Some(value.as_str()),
+                "header {name}"
+            );
+        }
+        for name in signed.signed_names() {
+            assert!(
+                name == "host" || request.headers.contains_key(format!("hahaha-{name}")),
+                "signed header {name} must be sent"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn packs_the_signed_request_into_an_envelope() {
+        let server = CaptureServer::start().await;
+        let signed = SignedHeaders::default();
+        let client = test_support::client(
+            ProxyInterceptorForLambda::new(
+                format!("{}/lambda", server.url),
+                LambdaEncoding::Envelope,
+            ),
+            signed.clone(),
+        );
+        test_support::get_item(&client).await;
+
+        let request = server.request();
+        assert_eq!(request.uri.path(), "/lambda");
+        assert_eq!(request.header("authorization"), None);
+        assert!(
+            request
+                .headers
+                .keys()
+                .all(|name| !name.as_str().starts_with("hahaha-"))
+        );
+
+        // The lambda gets back exactly what the signer produced
+        let received = request
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    value.to_str().expect("headers are text").to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let decoded = ddb_proxy_protocol::decode(received).expect("the envelope must decode");
+        assert_eq!(decoded.target_uri, format!("{DYNAMODB_ENDPOINT}/"));
+        let headers = signed.get();
+        assert!(headers.iter().any(|(name, _)| name == "authorization"));
+        for header in &headers {
+            assert!(decoded.headers.contains(header), "header {}", header.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn restored_requests_pass_signature_checks() {
+        for encoding in [LambdaEncoding::Renamed, LambdaEncoding::Envelope] {
+            let forwarder = Forwarder::start(test_support::restore_like_lambda).await;
+            let client = test_support::client(
+                ProxyInterceptorForLambda::new(forwarder.url.clone(), encoding),
+                SignedHeaders::default(),
+            );
+            test_support::put_and_get(&client)
+                .await
+                .unwrap_or_else(|e| panic!("{encoding:?} must pass signature checks: {e:?}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn broken_restoration_fails_signature_checks() {
+        fn keep_proxy_host(received: Vec<(String, String)>) -> (String, Vec<(String, String)>) {
+            let decoded = ddb_proxy_protocol::decode(received).expect("the request must decode");
+            (decoded.target_uri, decoded.headers)
+        }
+        fn change_a_signed_header(
+            received: Vec<(String, String)>,
+        ) -> (String, Vec<(String, String)>) {
+            let (target_uri, mut headers) = test_support::restore_like_lambda(received);
+            headers.retain(|(name, _)| name != "x-amz-target");
+            headers.push((
+                "x-amz-target".to_string(),
+                "DynamoDB_20120810.PutItem".into(),
+            ));
+            (target_uri, headers)
+        }
+        fn leave_renamed(received: Vec<(String, String)>) -> (String, Vec<(String, String)>) {
+            let (target_uri, _) = test_support::restore_like_lambda(received.clone());
+            (target_uri, received)
+        }
+
+        for restore in [keep_proxy_host, change_a_signed_header] {
+            for encoding in [LambdaEncoding::Renamed, LambdaEncoding::Envelope] {
+                let forwarder = Forwarder::start(restore).await;
+                let client = test_support::client(
+                    ProxyInterceptorForLambda::new(forwarder.url.clone(), encoding),
+                    SignedHeaders::default(),
+                );
+                let error = client
+                    .get_item()
+                    .table_name("users")
+                    .key(
+                        "user",
+                        aws_sdk_dynamodb::types::AttributeValue::S("alice".to_string()),
+                    )
+                    .send()
+                    .await
+                    .expect_err("a broken restoration must be rejected");
+                assert_eq!(
+                    error.code(),
+                    Some("InvalidSignatureException"),
+                    "{encoding:?}"
+                );
+            }
+        }
+
+        let forwarder = Forwarder::start(leave_renamed).await;
+        let client = test_support::client(
+            ProxyInterceptorForLambda::new(forwarder.url.clone(), LambdaEncoding::Renamed),
+            SignedHeaders::default(),
+        );
+        let error = test_support::put_and_get(&client)
+            .await
+            .expect_err("headers that weren't restored must be rejected");
+        assert_eq!(error.code(), Some("MissingAuthenticationTokenException"));
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::Instant;
+
+use aws_sdk_dynamodb::{
+    operation::update_item::UpdateItemError,
+    types::{AttributeValue, ReturnConsumedCapacity},
+};
+
+use crate::{
+    args::Args,
+    cache_status_interceptor::CacheStatus,
+    load_generator_task::get_user,
+    metrics::{Metrics, Operation},
+    ttl::Ttl,
+};
+
+/// How a read-modify-write ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Succeeded,
+    /// Every attempt lost the race to another writer
+    Exhausted,
+    /// A read or write failed for another reason than the condition
+    Failed,
+}
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Succeeded => "succeeded",
+            Outcome::Exhausted => "exhausted",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReadModifyWriteOptions {
+    /// Attempts after the first one that lost the race
+    pub max_retries: u32,
+    pub consistent_read: bool,
+}
+impl ReadModifyWriteOptions {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            max_retries: args.rmw_max_retries,
+            consistent_read: args.rmw_consistent_read,
+        }
+    }
+}
+
+/// Optimistic locking: read the item's `version` and `count`, then write `count + 1` and
+/// `version + 1` on the condition that `version` hasn't changed since the read.
+/// When another writer got there first the condition fails, and it starts over with a new read.
+///
+/// A stale read, like a cached one, always loses the race. Items it creates get the TTL attribute.
+pub async fn read_modify_write(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    user_id: AttributeValue,
+    options: ReadModifyWriteOptions,
+    ttl: Option<Ttl>,
+) {
+    let _in_flight = metrics.start_request();
+    let start = Instant::now();
+    let mut retries = 0;
+    let outcome = loop {
+        let Some(reply) =
+            get_user(&client, &metrics, user_id.clone(), options.consistent_read).await
+        else {
+            break Outcome::Failed;
+        };
+        let number = |name: &str| {
+            reply
+                .output
+                .item()
+                .and_then(|item| item.get(name))
+                .and_then(|value| value.as_n().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+        let version = number("version");
+        let count = number("count").unwrap_or_default();
+
+        let request = client
+            .update_item()
+            .table_name("users")
+            .key("user", user_id.clone())
+            .expression_attribute_names("#count", "count")
+            .expression_attribute_names("#version", "version")
+            .expression_attribute_values(":count", AttributeValue::N((count + 1).to_string()))
+            .expression_attribute_values(
+                ":next",
+                AttributeValue::N((version.unwrap_or_default() + 1).to_string()),
+            )
+            .return_consumed_capacity(ReturnConsumedCapacity::Total);
+        let request = match (version, &ttl) {
+            (Some(version), _) => request
+                .update_expression("SET #count = :count, #version = :next")
+                .condition_expression("#version = :expected")
+                .expression_attribute_values(":expected", AttributeValue::N(version.to_string())),
+            // The item is created here, so it expires like any other written item
+            (None, Some(ttl)) => {
+                let (attribute, expires) = ttl.expiry();
+                request
+                    .update_expression("SET #count = :count, #version = :next, #ttl = :expires")
+                    .condition_expression("attribute_not_exists(#version)")
+                    .expression_attribute_names("#ttl", attribute)
+                    .expression_attribute_values(":expires", expires)
+            }
+            (None, None) => request
+                .update_expression("SET #count = :count, #version = :next")
+                .condition_expression("attribute_not_exists(#version)"),
+        };
+        let write_start = Instant::now();
+        match request.send().await {
+            Ok(output) => {
+                metrics.record_latency(
+                    Operation::UpdateItem,
+                    write_start.elapsed(),
+                    CacheStatus::Absent,
+                );
+                metrics.record_write_capacity(output.consumed_capacity());
+                break Outcome::Succeeded;
+            }
+            Err(e) => {
+                // Losing the race is counted as a retry, not as an error
+                if !matches!(
+                    e.as_service_error(),
+                    Some(UpdateItemError::ConditionalCheckFailedException(_))
+                ) {
+                    metrics.record_error(Operation::UpdateItem, &e);
+                    log::error!("failed to update item: {e:#?}");
+                    break Outcome::Failed;
+                }
+                metrics.record_read_modify_write_conflict();
+                if retries == options.max_retries {
+                    break Outcome::Exhausted;
+                }
+                retries += 1;
+            }
+        }
+    };
+    metrics.record_read_modify_write(outcome, retries, start.elapsed());
+}
@@ -0,0 +1,527 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    cost::OnDemandPricing,
+    local_metrics::{self, Annotation, Snapshot},
+    metrics::Metrics,
+};
+
+pub const TIMESERIES_JSON: &str = "timeseries.jsonl";
+pub const TIMESERIES_CSV: &str = "timeseries.csv";
+pub const SUMMARY_JSON: &str = "summary.json";
+pub const SUMMARY_CSV: &str = "summary.csv";
+
+/// One line of `timeseries.jsonl`. Everything but `in_flight` covers only this interval.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IntervalRecord {
+    pub timestamp_ms: u64,
+    pub elapsed_seconds: f64,
+    pub target_tps: u32,
+    /// Completed operations per second, successful or not
+    pub achieved_tps: f64,
+    pub in_flight: i64,
+    pub overall: OperationStats,
+    pub operations: BTreeMap<String, OperationStats>,
+    /// Changes made to the run during this interval
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+/// `summary.json`, for the whole run.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Summary {
+    pub target: String,
+    pub command_line: String,
+    pub started_at_ms: u64,
+    pub duration_seconds: f64,
+    pub target_tps: u32,
+    pub achieved_tps: f64,
+    pub retries: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub read_capacity_units: f64,
+    pub write_capacity_units: f64,
+    pub estimated_cost_usd: f64,
+    pub overall: OperationStats,
+    pub operations: BTreeMap<String, OperationStats>,
+    pub errors_by_code: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// Whole paginated Query and Scan operations. Their pages are in `operations`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub paginated: BTreeMap<String, PaginatedStats>,
+    /// operation -> reason -> items, for canceled transactions
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub transaction_cancellation_reasons: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Only set if the mix had read-modify-writes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_modify_write: Option<ReadModifyWriteStats>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct PaginatedStats {
+    pub completed: u64,
+    pub pages: u64,
+    pub mean_pages: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+impl PaginatedStats {
+    fn by_operation(snapshot: &Snapshot) -> BTreeMap<String, PaginatedStats> {
+        snapshot
+            .label_values("paginated_latency", "operation")
+            .into_iter()
+            .map(|operation| {
+                let matches =
+                    |labels: &BTreeMap<String, String>| labels.get("operation") == Some(&operation);
+                let latency = snapshot.histogram("paginated_latency", matches);
+                let pages = snapshot.counter("pages", matches);
+                let completed = latency.count();
+                let stats = PaginatedStats {
+                    completed,
+                    pages,
+                    mean_pages: if completed == 0 {
+                        0.0
+                    } else {
+                        pages as f64 / completed as f64
+                    },
+                    mean_ms: millis(latency.mean()),
+                    p50_ms: millis(latency.percentile(0.5)),
+                    p99_ms: millis(latency.percentile(0.99)),
+                    max_ms: millis(latency.max()),
+                };
+                (operation, stats)
+            })
+            .filter(|(_, stats)| 0 < stats.completed)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReadModifyWriteStats {
+    pub succeeded: u64,
+    /// Gave up after losing the race on every attempt
+    pub exhausted: u64,
+    pub failed: u64,
+    /// Attempts after the first one, because the conditional write lost the race
+    pub retries: u64,
+    /// Conditional check failures out of all UpdateItem attempts: successful, lost races, and
+    /// failed for another reason
+    pub conditional_check_failure_rate: f64,
+    /// From the first read to the successful write, including retries
+    pub success_mean_ms: f64,
+    pub success_p50_ms: f64,
+    pub success_p99_ms: f64,
+    pub success_max_ms: f64,
+}
+impl ReadModifyWriteStats {
+    fn new(snapshot: &Snapshot) -> Option<Self> {
+        let outcome = |outcome: &'static str| {
+            snapshot.counter("read_modify_writes", |labels| {
+                labels.get("outcome").map(String::as_str) == Some(outcome)
+            })
+        };
+        let (succeeded, exhausted, failed) = (
+            outcome("succeeded"),
+            outcome("exhausted"),
+            outcome("failed"),
+        );
+        if succeeded + exhausted + failed == 0 {
+            return None;
+        }
+        let update_item = |labels: &BTreeMap<String, String>| {
+            labels.get("operation").map(String::as_str) == Some("UpdateItem")
+        };
+        // Lost races are counted on their own, not as UpdateItem errors
+        let conflicts = snapshot.counter("read_modify_write_conflicts", |_| true);
+        let updates = snapshot.counter("requests", update_item)
+            + snapshot.counter("errors", update_item)
+            + conflicts;
+        let latency = snapshot.histogram("read_modify_write_latency", |_| true);
+        Some(Self {
+            succeeded,
+            exhausted,
+            failed,
+            retries: snapshot.counter("read_modify_write_retries", |_| true),
+            conditional_check_failure_rate: if updates == 0 {
+                0.0
+            } else {
+                conflicts as f64 / updates as f64
+            },
+            success_mean_ms: millis(latency.mean()),
+            success_p50_ms: millis(latency.percentile(0.5)),
+            success_p99_ms: millis(latency.percentile(0.99)),
+            success_max_ms: millis(latency.max()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct OperationStats {
+    /// Successful requests
+    pub requests: u64,
+    pub errors: u64,
+    pub tps: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub max_ms: f64,
+}
+impl OperationStats {
+    fn new(snapshot: &Snapshot, operation: Option<&str>, seconds: f64) -> Self {
+        let matches = |labels: &BTreeMap<String, String>| {
+            operation.is_none_or(|operation| {
+                labels.get("operation").map(String::as_str) == Some(operation)
+            })
+        };
+        let latency = snapshot.histogram("latency", matches);
+        let requests = snapshot.counter("requests", matches);
+        let errors = snapshot.counter("errors", matches);
+        Self {
+            requests,
+            errors,
+            tps: if 0.0 < seconds {
+                (requests + errors) as f64 / seconds
+            } else {
+                0.0
+            },
+            mean_ms: millis(latency.mean()),
+            p50_ms: millis(latency.percentile(0.5)),
+            p90_ms: millis(latency.percentile(0.9)),
+            p99_ms: millis(latency.percentile(0.99)),
+            p999_ms: millis(latency.percentile(0.999)),
+            max_ms: millis(latency.max()),
+        }
+    }
+
+    fn by_operation(snapshot: &Snapshot, seconds: f64) -> BTreeMap<String, OperationStats> {
+        snapshot
+            .label_values("requests", "operation")
+            .into_iter()
+            .map(|operation| {
+                let stats = OperationStats::new(snapshot, Some(&operation), seconds);
+                (operation, stats)
+            })
+            .filter(|(_, stats)| 0 < stats.requests + stats.errors)
+            .collect()
+    }
+}
+
+/// Flat row of `timeseries.csv`. There is one row per operation per interval.
+/// The csv writer can't flatten structs, so the stats are written out as a tuple.
+#[derive(serde::Serialize)]
+struct TimeseriesRow<'a> {
+    timestamp_ms: u64,
+    elapsed_seconds: f64,
+    target_tps: u32,
+    achieved_tps: f64,
+    in_flight: i64,
+    operation: &'a str,
+}
+
+/// Flat row of `summary.csv`. There is one row per operation, plus `all`.
+#[derive(serde::Serialize)]
+struct SummaryRow<'a> {
+    target: &'a str,
+    duration_seconds: f64,
+    operation: &'a str,
+}
+
+const STATS_HEADER: [&str; 9] = [
+    "requests", "errors", "tps", "mean_ms", "p50_ms", "p90_ms", "p99_ms", "p999_ms", "max_ms",
+];
+
+/// Writes a time series while the test runs, and a summary when it is done.
+pub struct ResultsWriter {
+    directory: PathBuf,
+    timeseries_json: BufWriter<File>,
+    timeseries_csv: csv::Writer<File>,
+    previous: Snapshot,
+    previous_at: Instant,
+    annotations_written: usize,
+}
+impl ResultsWriter {
+    pub fn create(directory: &Path) -> Self {
+        std::fs::create_dir_all(directory).expect("must be able to create the results directory");
+        log::info!("writing results to {}", directory.display());
+        Self {
+            directory: directory.to_path_buf(),
+            timeseries_json: BufWriter::new(
+                File::create(directory.join(TIMESERIES_JSON))
+                    .expect("must be able to create the json time series"),
+            ),
+            timeseries_csv: csv_writer(
+                &directory.join(TIMESERIES_CSV),
+                &[
+                    "timestamp_ms",
+                    "elapsed_seconds",
+                    "target_tps",
+                    "achieved_tps",
+                    "in_flight",
+                    "operation",
+                ],
+            ),
+            previous: Snapshot::default(),
+            previous_at: Instant::now(),
+            annotations_written: 0,
+        }
+    }
+
+    /// Record the interval since the previous one. `snapshot` is cumulative from the start of the
+    /// run, like [`local_metrics::Registry::snapshot`] or a merge of them.
+    pub fn record_interval(&mut self, snapshot: Snapshot, started: Instant, target_tps: u32) {
+        let now = Instant::now();
+        let interval = snapshot.since(&self.previous);
+        let seconds = now.duration_since(self.previous_at).as_secs_f64();
+        self.previous = snapshot;
+        self.previous_at = now;
+
+        let annotations = local_metrics::default_registry().annotations(self.annotations_written);
+        self.annotations_written += annotations.len();
+
+        let overall = OperationStats::new(&interval, None, seconds);
+        let record = IntervalRecord {
+            timestamp_ms: unix_millis(),
+            elapsed_seconds: now.duration_since(started).as_secs_f64(),
+            target_tps,
+            achieved_tps: overall.tps,
+            in_flight: interval.gauge("in_flight", |_| true),
+            operations: OperationStats::by_operation(&interval, seconds),
+            overall,
+            annotations,
+        };
+
+        serde_json::to_writer(&mut self.timeseries_json, &record)
+            .expect("must be able to write the json time series");
+        self.timeseries_json
+            .write_all(b"\n")
+            .and_then(|_| self.timeseries_json.flush())
+            .expect("must be able to write the json time series");
+        for (operation, stats) in &record.operations {
+            self.timeseries_csv
+                .serialize((
+                    TimeseriesRow {
+                        timestamp_ms: record.timestamp_ms,
+                        elapsed_seconds: record.elapsed_seconds,
+                        target_tps: record.target_tps,
+                        achieved_tps: record.achieved_tps,
+                        in_flight: record.in_flight,
+                        operation,
+                    },
+                    stats,
+                ))
+                .expect("must be able to write the csv time series");
+        }
+        self.timeseries_csv
+            .flush()
+            .expect("must be able to write the csv time series");
+    }
+
+    pub fn finish(mut self, summary: &Summary) {
+        self.timeseries_json
+            .flush()
+            .expect("must be able to write the json time series");
+        serde_json::to_writer_pretty(
+            File::create(self.directory.join(SUMMARY_JSON))
+                .expect("must be able to create the json summary"),
+            summary,
+        )
+        .expect("must be able to write the json summary");
+
+        let mut csv = csv_writer(
+            &self.directory.join(SUMMARY_CSV),
+            &["target", "duration_seconds", "operation"],
+        );
+        for (operation, stats) in summary
+            .operations
+            .iter()
+            .map(|(operation, stats)| (operation.as_str(), stats))
+            .chain([("all", &summary.overall)])
+        {
+            csv.serialize((
+                SummaryRow {
+                    target: &summary.target,
+                    duration_seconds: summary.duration_seconds,
+                    operation,
+                },
+                stats,
+            ))
+            .expect("must be able to write the csv summary");
+        }
+        csv.flush().expect("must be able to write the csv summary");
+        log::info!("wrote results summary to {}", self.directory.display());
+    }
+}
+
+/// Summarize the run so far from the local registry.
+pub fn summarize(
+    metrics: &Metrics,
+    pricing: OnDemandPricing,
+    started: Instant,
+    started_at_ms: u64,
+    target_tps: u32,
+) -> Summary {
+    summarize_snapshot(
+        &local_metrics::default_registry().snapshot(),
+        metrics.target(),
+        pricing,
+        started.elapsed().as_secs_f64(),
+        started_at_ms,
+        target_tps,
+    )
+}
+
+/// Summarize a cumulative snapshot, which may be merged from several processes.
+pub fn summarize_snapshot(
+    snapshot: &Snapshot,
+    target: &str,
+    pricing: OnDemandPricing,
+    duration_seconds: f64,
+    started_at_ms: u64,
+    target_tps: u32,
+) -> Summary {
+    let all = |_: &BTreeMap<String, String>| true;
+    let read_capacity_units =
+        snapshot.counter("consumed_read_capacity_milliunits", all) as f64 / 1000.0;
+    let write_capacity_units =
+        snapshot.counter("consumed_write_capacity_milliunits", all) as f64 / 1000.0;
+    let overall = OperationStats::new(snapshot, None, duration_seconds);
+    let cache = |status: &'static str| {
+        move |labels: &BTreeMap<String, String>| {
+            labels.get("cache").map(String::as_str) == Some(status)
+        }
+    };
+    Summary {
+        target: target.to_string(),
+        command_line: std::env::args().collect::<Vec<_>>().join(" "),
+        started_at_ms,
+        duration_seconds,
+        target_tps,
+        achieved_tps: overall.tps,
+        retries: snapshot.counter("retries", all),
+        cache_hits: snapshot.counter("requests", cache("hit")),
+        cache_misses: snapshot.counter("requests", cache("miss")),
+        read_capacity_units,
+        write_capacity_units,
+        estimated_cost_usd: pricing.estimate(read_capacity_units, write_capacity_units),
+        operations: OperationStats::by_operation(snapshot, duration_seconds),
+        errors_by_code: snapshot
+            .label_values("errors", "error")
+            .into_iter()
+            .map(|code| {
+                let count = snapshot.counter("errors", |labels| labels.get("error") == Some(&code));
+                (code, count)
+            })
+            .collect(),
+        annotations: local_metrics::default_registry().annotations(0),
+        paginated: PaginatedStats::by_operation(snapshot),
+        transaction_cancellation_reasons: snapshot
+            .label_values("transaction_cancellation_reasons", "operation")
+            .into_iter()
+            .map(|operation| {
+                let reasons = snapshot
+                    .label_values("transaction_cancellation_reasons", "reason")
+                    .into_iter()
+                    .map(|reason| {
+                        let count =
+                            snapshot.counter("transaction_cancellation_reasons", |labels| {
+                                labels.get("operation") == Some(&operation)
+                                    && labels.get("reason") == Some(&reason)
+                            });
+                        (reason, count)
+                    })
+                    .filter(|(_, count)| 0 < *count)
+                    .collect();
+                (operation, reasons)
+            })
+            .collect(),
+        read_modify_write: ReadModifyWriteStats::new(snapshot),
+        overall,
+    }
+}
+
+/// A csv writer with the header already written, since tuples of structs don't get one.
+fn csv_writer(path: &Path, row_header: &[&str]) -> csv::Writer<File> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_path(path)
+        .expect("must be able to create a csv file");
+    writer
+        .write_record(row_header.iter().chain(STATS_HEADER.iter()))
+        .expect("must be able to write a csv header");
+    writer
+}
+
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn millis(nanos: u64) -> f64 {
+    nanos as f64 / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::local_metrics::{SeriesSnapshot, SeriesValue, Snapshot};
+
+    use super::ReadModifyWriteStats;
+
+    fn counter(name: &str, labels: &[(&str, &str)], count: u64) -> SeriesSnapshot {
+        SeriesSnapshot {
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            value: SeriesValue::Counter(count),
+        }
+    }
+
+    #[test]
+    fn conflicts_count_towards_the_conditional_check_failure_rate() {
+        let snapshot = Snapshot {
+            series: vec![
+                counter("read_modify_writes", &[("outcome", "succeeded")], 6),
+                counter("read_modify_writes", &[("outcome", "exhausted")], 1),
+                counter("read_modify_write_retries", &[], 3),
+                counter("read_modify_write_conflicts", &[], 3),
+                counter("requests", &[("operation", "UpdateItem")], 6),
+                counter(
+                    "errors",
+                    &[
+                        ("operation", "UpdateItem"),
+                        ("error", "InternalServerError"),
+                    ],
+                    1,
+                ),
+                // Reads aren't conditional writes
+                counter("requests", &[("operation", "GetItem")], 10),
+            ],
+        };
+        let stats = ReadModifyWriteStats::new(&snapshot).expect("there were read-modify-writes");
+        assert_eq!((stats.succeeded, stats.exhausted, stats.failed), (6, 1, 0));
+        assert_eq!(stats.retries, 3);
+        assert_eq!(stats.conditional_check_failure_rate, 0.3);
+    }
+
+    #[test]
+    fn runs_without_read_modify_writes_have_no_stats() {
+        let snapshot = Snapshot {
+            series: vec![counter("requests", &[("operation", "UpdateItem")], 6)],
+        };
+        assert!(ReadModifyWriteStats::new(&snapshot).is_none());
+    }
+}
@@ -0,0 +1,41 @@
+use aws_smithy_runtime_api::client::retries::RequestAttempts;
+
+use crate::metrics::Metrics;
+
+/// Counts every attempt after the first, so retries are visible instead of hiding
+/// inside of the measured latency.
+#[derive(Clone)]
+pub struct RetryInterceptor {
+    metrics: Metrics,
+}
+impl std::fmt::Debug for RetryInterceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryInterceptor").finish_non_exhaustive()
+    }
+}
+impl RetryInterceptor {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
+impl aws_sdk_dynamodb::config::Intercept for RetryInterceptor {
+    fn name(&self) -> &'static str {
+        "Retry"
+    }
+
+    fn read_before_attempt(
+        &self,
+        _context: &aws_sdk_dynamodb::config::interceptors::BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &aws_sdk_dynamodb::config::RuntimeComponents,
+        cfg: &mut aws_sdk_dynamodb::config::ConfigBag,
+    ) -> Result<(), aws_sdk_dynamodb::error::BoxError> {
+        if let Some(attempts) = cfg.load::<RequestAttempts>()
+            && 1 < attempts.attempts()
+        {
+            log::debug!("retry attempt #{attempts}", attempts = attempts.attempts());
+            self.metrics.record_retry();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+//! A local server that records what the tester sends, for testing interceptors with a real client.
+
+use std::sync::{Arc, Mutex};
+
+use aws_sdk_dynamodb::config::{
+    BehaviorVersion, ConfigBag, Credentials, Intercept, Region, RuntimeComponents,
+    interceptors::BeforeTransmitInterceptorContextRef, retry::RetryConfig,
+};
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, Method, Uri},
+};
+
+/// The endpoint the client thinks it is talking to. Interceptors redirect requests away from it,
+/// so nothing is sent to AWS.
+pub const DYNAMODB_ENDPOINT: &str = "https://dynamodb.us-west-2.amazonaws.com";
+
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+impl CapturedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(name)
+            .map(|value| value.to_str().expect("headers are text"))
+    }
+}
+
+/// Answers every request with an empty DynamoDB response, and keeps what it received.
+pub struct CaptureServer {
+    /// Like http://127.0.0.1:1234
+    pub url: String,
+    requests: Arc<Mutex<Vec<CapturedRequest>>>,
+}
+impl CaptureServer {
+    pub async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("must bind a local port");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("must have an address")
+        );
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let app = axum::Router::new().fallback({
+            let requests = requests.clone();
+            move |method: Method, uri: Uri, headers: HeaderMap, body: Bytes| async move {
+                requests
+                    .lock()
+                    .expect("local mutex works")
+                    .push(CapturedRequest {
+                        method,
+                        uri,
+                        headers,
+                        body,
+                    });
+                (
+                    [("content-type", "application/x-amz-json-1.0")],
+                    "{}".to_string(),
+                )
+            }
+        });
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Self { url, requests }
+    }
+
+    /// The one request the server received.
+    pub fn request(&self) -> CapturedRequest {
+        let requests = self.requests.lock().expect("local mutex works");
+        assert_eq!(requests.len(), 1, "expected exactly one request");
+        requests[0].clone()
+    }
+}
+
+/// Records the headers of a request as the signer left them, before any interceptor changed them.
+#[derive(Debug, Clone, Default)]
+pub struct SignedHeaders {
+    headers: Arc<Mutex<Vec<(String, String)>>>,
+}
+impl SignedHeaders {
+    pub fn get(&self) -> Vec<(String, String)> {
+        self.headers.lock().expect("local mutex works").clone()
+    }
+
+    /// The headers the `Authorization` header says were signed.
+    pub fn signed_names(&self) -> Vec<String> {
+        let headers = self.get();
+        let authorization = headers
+            .iter()
+            .find(|(name, _)| name == "authorization")
+            .map(|(_, value)| value)
+            .expect("the request must be signed");
+        authorization
+            .split("SignedHeaders=")
+            .nth(1)
+            .and_then(|rest| rest.split(',').next())
+            .expect("the authorization header must list the signed headers")
+            .split(';')
+            .map(str::to_string)
+            .collect()
+    }
+}
+impl Intercept for SignedHeaders {
+    fn name(&self) -> &'static str {
+        "SignedHeaders"
+    }
+
+    fn read_after_signing(
+        &self,
+        context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), aws_sdk_dynamodb::error::BoxError> {
+        *self.headers.lock().expect("local mutex works") = context
+            .request()
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Ok(())
+    }
+}
+
+/// A client for [`DYNAMODB_ENDPOINT`] with static credentials and the interceptor under test.
+pub fn client(
+    interceptor: impl Intercept + 'static,
+    signed: SignedHeaders,
+) -> aws_sdk_dynamodb::Client {
+    aws_sdk_dynamodb::Client::from_conf(
+        aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-west-2"))
+            .credentials_provider(Credentials::new("AKIDTEST", "secret", None, None, "test"))
+            .endpoint_url(DYNAMODB_ENDPOINT)
+            .retry_config(RetryConfig::disabled())
+            .interceptor(signed)
+            .interceptor(interceptor)
+            .build(),
+    )
+}
+
+/// Send a GetItem through the client.
+pub async fn get_item(client: &aws_sdk_dynamodb::Client) {
+    client
+        .get_item()
+        .table_name("users")
+        .key(
+            "user",
+            aws_sdk_dynamodb::types::AttributeValue::S("alice".to_string()),
+        )
+        .send()
+        .await
+        .expect("the capture server must answer");
+}
+
+/// Turns the headers a proxy received back into the target uri and the headers to forward.
+pub type Restore = fn(Vec<(String, String)>) -> (String, Vec<(String, String)>);
+
+/// A proxy between an interceptor and a local DynamoDB stand-in that checks signatures. It
+/// restores each request with a [`Restore`] and forwards it, like the lambda does.
+pub struct Forwarder {
+    /// Like http://127.0.0.1:1234
+    pub url: String,
+}
+impl Forwarder {
+    pub async fn start(restore: Restore) -> Self {
+        let stand_in = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("must bind a local port");
+        let stand_in_url = format!(
+            "http://{}",
+            stand_in.local_addr().expect("must have an address")
+        );
+        tokio::spawn(ddb_local::serve(
+            stand_in,
+            ddb_local::Config {
+                credentials: Some(ddb_local::Credentials::new("AKIDTEST", "secret")),
+                ..ddb_local::Config::default()
+            },
+        ));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("must bind a local port");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("must have an address")
+        );
+        let app = axum::Router::new().fallback(move |headers: HeaderMap, body: Bytes| {
+            let stand_in_url = stand_in_url.clone();
+            async move {
+                let received = headers
+                    .iter()
+                    .map(|(name, value)| {
+                        (
+                            name.to_string(),
+                            value.to_str().expect("headers are text").to_string(),
+                        )
+                    })
+                    .collect();
+                let (target_uri, headers) = restore(received);
+                let target: Uri = target_uri.parse().expect("the target must be a uri");
+                let mut request = reqwest::Client::new()
+                    .post(format!("{stand_in_url}{}", target.path()))
+                    .body(body);
+                for (name, value) in headers {
+                    request = request.header(name, value);
+                }
+                let response = request.send().await.expect("the stand-in must answer");
+                let status = response.status();
+                let content_type = response
+                    .headers()
+                    .get("content-type")
+                    .cloned()
+                    .expect("the stand-in sets a content type");
+                let body = response.bytes().await.expect("the stand-in must answer");
+                (status, [("content-type", content_type)], body)
+            }
+        });
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Self { url }
+    }
+}
+
+/// Restore a request like the lambda: decode it, then send it to the target's host.
+pub fn restore_like_lambda(received: Vec<(String, String)>) -> (String, Vec<(String, String)>) {
+    let ddb_proxy_protocol::Decoded {
+        target_uri,
+        mut headers,
+    } = ddb_proxy_protocol::decode(received).expect("the request must decode");
+    let host = target_uri
+        .parse::<Uri>()
+        .expect("the target must be a uri")
+        .host()
+        .expect("the target has a host")
+        .to_string();
+    headers.retain(|(name, _)| !name.eq_ignore_ascii_case("host"));
+    headers.push(("host".to_string(), host));
+    (target_uri, headers)
+}
+
+/// Put an item and read it back through the client.
+pub async fn put_and_get(client: &aws_sdk_dynamodb::Client) -> Result<(), aws_sdk_dynamodb::Error> {
+    use aws_sdk_dynamodb::types::AttributeValue;
+
+    client
+        .put_item()
+        .table_name("users")
+        .item("user", AttributeValue::S("alice".to_string()))
+        .item("value", AttributeValue::S("a, b".to_string()))
+        .send()
+        .await?;
+    let item = client
+        .get_item()
+        .table_name("users")
+        .key("user", AttributeValue::S("alice".to_string()))
+        .send()
+        .await?
+        .item
+        .expect("the item must be there");
+    assert_eq!(
+        item.get("value"),
+        Some(&AttributeValue::S("a, b".to_string()))
+    );
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::time::Instant;
+
+use aws_sdk_dynamodb::config::{
+    ConfigBag, RuntimeComponents,
+    interceptors::{
+        AfterDeserializationInterceptorContextRef, BeforeDeserializationInterceptorContextRef,
+        BeforeSerializationInterceptorContextRef, BeforeTransmitInterceptorContextRef,
+    },
+};
+use aws_sdk_dynamodb::error::BoxError;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+
+use crate::metrics::Metrics;
+
+/// The parts of an sdk request that are timed separately from the end-to-end latency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Turning the request input into an http request
+    Serialization,
+    /// Sigv4 signing
+    Signing,
+    /// From the last `modify_before_transmit` until the response arrives. This is the network
+    /// and whatever is on the other side of it, like an accelerator.
+    Transmit,
+    /// Turning the http response into the operation output
+    Deserialization,
+}
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Serialization => "serialization",
+            Phase::Signing => "signing",
+            Phase::Transmit => "transmit",
+            Phase::Deserialization => "deserialization",
+        }
+    }
+}
+
+/// Records how long each request phase takes. The phases of a request never overlap, so
+/// one start time in the request's config bag is enough to time all of them.
+#[derive(Clone)]
+pub struct TimingInterceptor {
+    metrics: Metrics,
+}
+impl std::fmt::Debug for TimingInterceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimingInterceptor").finish_non_exhaustive()
+    }
+}
+impl TimingInterceptor {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+
+    fn start(&self, cfg: &mut ConfigBag) {
+        cfg.interceptor_state()
+            .store_put(PhaseStart(Instant::now()));
+    }
+
+    fn finish(&self, phase: Phase, cfg: &mut ConfigBag) {
+        match cfg.load::<PhaseStart>() {
+            Some(PhaseStart(start)) => self.metrics.record_phase_latency(phase, start.elapsed()),
+            None => log::warn!("no start time for {phase}", phase = phase.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PhaseStart(Instant);
+impl Storable for PhaseStart {
+    type Storer = StoreReplace<Self>;
+}
+
+impl aws_sdk_dynamodb::config::Intercept for TimingInterceptor {
+    fn name(&self) -> &'static str {
+        "Timing"
+    }
+
+    fn read_before_serialization(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.start(cfg);
+        Ok(())
+    }
+
+    fn read_after_serialization(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.finish(Phase::Serialization, cfg);
+        Ok(())
+    }
+
+    fn read_before_signing(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.start(cfg);
+        Ok(())
+    }
+
+    fn read_after_signing(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.finish(Phase::Signing, cfg);
+        Ok(())
+    }
+
+    fn read_before_transmit(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        // Every interceptor's modify_before_transmit has run by now, including the proxy rewrites.
+        self.start(cfg);
+        Ok(())
+    }
+
+    fn read_after_transmit(
+        &self,
+        _context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.finish(Phase::Transmit, cfg);
+        Ok(())
+    }
+
+    fn read_before_deserialization(
+        &self,
+        _context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.start(cfg);
+        Ok(())
+    }
+
+    fn read_after_deserialization(
+        &self,
+        _context: &AfterDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        self.finish(Phase::Deserialization, cfg);
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Instant;
+
+use aws_sdk_dynamodb::{
+    operation::{
+        transact_get_items::TransactGetItemsError, transact_write_items::TransactWriteItemsError,
+    },
+    types::{
+        AttributeValue, CancellationReason, Get, Put, ReturnConsumedCapacity, TransactGetItem,
+        TransactWriteItem,
+    },
+};
+
+use crate::{
+    args::Args,
+    cache_status_interceptor::CacheStatus,
+    load_generator_task::user_item,
+    metrics::{Metrics, Operation},
+    ttl::Ttl,
+};
+
+/// DynamoDB's limit on items per transaction
+const MAX_TRANSACTION_ITEMS: usize = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionOptions {
+    pub items: usize,
+}
+impl TransactionOptions {
+    pub fn new(args: &Args) -> Self {
+        assert!(
+            (1..=MAX_TRANSACTION_ITEMS).contains(&args.transaction_items),
+            "--transaction-items must be from 1 to {MAX_TRANSACTION_ITEMS}"
+        );
+        Self {
+            items: args.transaction_items,
+        }
+    }
+}
+
+pub async fn transact_get(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    keys: Vec<AttributeValue>,
+) {
+    let _in_flight = metrics.start_request();
+    let request = client
+        .transact_get_items()
+        .set_transact_items(Some(
+            keys.into_iter()
+                .map(|key| {
+                    TransactGetItem::builder()
+                        .get(
+                            Get::builder()
+                                .table_name("users")
+                                .key("user", key)
+                                .build()
+                                .expect("must be a complete get"),
+                        )
+                        .build()
+                })
+                .collect(),
+        ))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total);
+    let start = Instant::now();
+    match request.send().await {
+        Ok(output) => {
+            metrics.record_latency(
+                Operation::TransactGetItems,
+                start.elapsed(),
+                CacheStatus::Absent,
+            );
+            for capacity in output.consumed_capacity() {
+                metrics.record_read_capacity(Some(capacity));
+            }
+        }
+        Err(e) => {
+            metrics.record_error(Operation::TransactGetItems, &e);
+            match e.as_service_error() {
+                Some(TransactGetItemsError::TransactionCanceledException(canceled)) => {
+                    record_cancellation(
+                        &metrics,
+                        Operation::TransactGetItems,
+                        canceled.cancellation_reasons(),
+                    )
+                }
+                _ => log::error!("failed to transact get items: {e:#?}"),
+            }
+        }
+    }
+}
+
+pub async fn transact_write(
+    client: aws_sdk_dynamodb::Client,
+    metrics: Metrics,
+    keys: Vec<AttributeValue>,
+    ttl: Option<Ttl>,
+) {
+    let _in_flight = metrics.start_request();
+    let request = client
+        .transact_write_items()
+        .set_transact_items(Some(
+            keys.into_iter()
+                .map(|key| {
+                    TransactWriteItem::builder()
+                        .put(
+                            Put::builder()
+                                .table_name("users")
+                                .set_item(Some(user_item(key, ttl.as_ref())))
+                                .build()
+                                .expect("must be a complete put"),
+                        )
+                        .build()
+                })
+                .collect(),
+        ))
+        .return_consumed_capacity(ReturnConsumedCapacity::Total);
+    let start = Instant::now();
+    match request.send().await {
+        Ok(output) => {
+            metrics.record_latency(
+                Operation::TransactWriteItems,
+                start.elapsed(),
+                CacheStatus::Absent,
+            );
+            for capacity in output.consumed_capacity() {
+                metrics.record_write_capacity(Some(capacity));
+            }
+        }
+        Err(e) => {
+            metrics.record_error(Operation::TransactWriteItems, &e);
+            match e.as_service_error() {
+                Some(TransactWriteItemsError::TransactionCanceledException(canceled)) => {
+                    record_cancellation(
+                        &metrics,
+                        Operation::TransactWriteItems,
+                        canceled.cancellation_reasons(),
+                    )
+                }
+                _ => log::error!("failed to transact write items: {e:#?}"),
+            }
+        }
+    }
+}
+
+/// Each item of a canceled transaction has a reason. `None` means that item was fine.
+fn record_cancellation(metrics: &Metrics, operation: Operation, reasons: &[CancellationReason]) {
+    log::debug!("{} canceled: {reasons:?}", operation.as_str());
+    for reason in reasons {
+        match reason.code() {
+            Some("None") => (),
+            code => metrics.record_cancellation_reason(operation, code.unwrap_or("unknown")),
+        }
+    }
+}
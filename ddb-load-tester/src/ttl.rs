@@ -0,0 +1,132 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use rand::Rng;
+
+use crate::{args::Args, results};
+
+/// How long written items live, in seconds. Negative lifetimes write items that are already
+/// expired.
+///
+/// * `3600` lives exactly an hour
+/// * `60..3600` lives from a minute to an hour, uniformly
+/// * `exp:600` lives 10 minutes on average, exponentially distributed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lifetime {
+    Fixed(f64),
+    Uniform(f64, f64),
+    Exponential(f64),
+}
+impl Lifetime {
+    fn sample(self, random: &mut impl Rng) -> f64 {
+        match self {
+            Lifetime::Fixed(seconds) => seconds,
+            Lifetime::Uniform(from, to) => random.random_range(from..=to),
+            Lifetime::Exponential(mean) => -mean * (1.0 - random.random::<f64>()).ln(),
+        }
+    }
+}
+impl FromStr for Lifetime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let seconds = |s: &str| {
+            let seconds = s
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("bad lifetime {s}: {e}"))?;
+            if !seconds.is_finite() {
+                return Err(format!(
+                    "bad lifetime {s}: must be a finite number of seconds"
+                ));
+            }
+            Ok(seconds)
+        };
+        if let Some(mean) = s.strip_prefix("exp:") {
+            let mean = seconds(mean)?;
+            if mean <= 0.0 {
+                return Err("an exponential lifetime needs a mean above 0".to_string());
+            }
+            Ok(Lifetime::Exponential(mean))
+        } else if let Some((from, to)) = s.split_once("..") {
+            let (from, to) = (seconds(from)?, seconds(to)?);
+            if to < from {
+                return Err(format!("the lifetime range {s} is empty"));
+            }
+            Ok(Lifetime::Uniform(from, to))
+        } else {
+            Ok(Lifetime::Fixed(seconds(s)?))
+        }
+    }
+}
+
+/// Sets a DynamoDB TTL attribute on written items, to the unix time in seconds when they expire.
+#[derive(Debug, Clone)]
+pub struct Ttl {
+    attribute: Arc<str>,
+    lifetime: Lifetime,
+}
+impl Ttl {
+    pub fn new(args: &Args) -> Option<Self> {
+        args.ttl_attribute.as_deref().map(|attribute| Self {
+            attribute: attribute.into(),
+            lifetime: args.ttl_lifetime,
+        })
+    }
+
+    /// The attribute to write with a new item.
+    pub fn expiry(&self) -> (String, AttributeValue) {
+        let now = results::unix_millis() as f64 / 1000.0;
+        let expires = now + self.lifetime.sample(&mut rand::rng());
+        (
+            self.attribute.to_string(),
+            AttributeValue::N((expires.round() as i64).to_string()),
+        )
+    }
+
+    /// Whether an item that was read has outlived its TTL. DynamoDB deletes expired items some
+    /// time after they expire, so reads can still find them.
+    pub fn expired(&self, item: &HashMap<String, AttributeValue>) -> bool {
+        let Some(AttributeValue::N(expires)) = item.get(&*self.attribute) else {
+            return false;
+        };
+        expires
+            .parse::<f64>()
+            .is_ok_and(|expires| expires * 1000.0 < results::unix_millis() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lifetime;
+
+    #[test]
+    fn lifetimes_parse() {
+        assert_eq!("3600".parse(), Ok(Lifetime::Fixed(3600.0)));
+        assert_eq!("-60".parse(), Ok(Lifetime::Fixed(-60.0)));
+        assert_eq!("60..3600".parse(), Ok(Lifetime::Uniform(60.0, 3600.0)));
+        assert_eq!(" 60 .. 60 ".parse(), Ok(Lifetime::Uniform(60.0, 60.0)));
+        assert_eq!("-120..-60".parse(), Ok(Lifetime::Uniform(-120.0, -60.0)));
+        assert_eq!("exp:600".parse(), Ok(Lifetime::Exponential(600.0)));
+    }
+
+    #[test]
+    fn bad_lifetimes_are_rejected() {
+        for bad in [
+            "", "soon", "3600..60", "exp:0", "exp:-1", "nan", "inf", "-inf", "NaN..1", "1..inf",
+            "exp:inf", "exp:nan",
+        ] {
+            assert!(bad.parse::<Lifetime>().is_err(), "{bad} must be rejected");
+        }
+    }
+
+    #[test]
+    fn samples_stay_in_range() {
+        let mut random = rand::rng();
+        for _ in 0..1000 {
+            assert_eq!(Lifetime::Fixed(10.0).sample(&mut random), 10.0);
+            assert!((5.0..=10.0).contains(&Lifetime::Uniform(5.0, 10.0).sample(&mut random)));
+            assert!(0.0 <= Lifetime::Exponential(10.0).sample(&mut random));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+};
+use tokio::sync::{Notify, oneshot};
+
+use crate::{
+    args::Args,
+    local_metrics::{self, Snapshot},
+    results,
+};
+
+/// The share of a distributed run that a coordinator gives to one worker.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Assignment {
+    pub worker: usize,
+    pub tps: u32,
+    pub seed: u64,
+    pub items: u64,
+    pub item_key_length: usize,
+    pub shard_index: u64,
+    pub shard_count: u64,
+    pub shard_overlap: f64,
+    pub partition_workers: bool,
+    pub populate: bool,
+    pub mix: String,
+    pub duration_seconds: u64,
+}
+
+/// When every worker starts sending, once they are all ready.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Start {
+    /// Unix time in milliseconds
+    pub start_at_ms: u64,
+}
+impl Assignment {
+    /// Replace the run options the coordinator decides.
+    pub fn apply(&self, args: &mut Args) {
+        args.tps = self.tps;
+        args.seed = self.seed;
+        args.items = self.items;
+        args.item_key_length = self.item_key_length;
+        args.shard_index = self.shard_index;
+        args.shard_count = self.shard_count;
+        args.shard_overlap = self.shard_overlap;
+        args.partition_workers = self.partition_workers;
+        args.populate = self.populate;
+        args.mix = self
+            .mix
+            .parse()
+            .expect("the coordinator must send a valid mix");
+        args.duration = Some(self.duration_seconds);
+    }
+}
+
+/// What a worker has recorded so far.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WorkerReport {
+    /// Set once the worker finished its assignment, so the snapshot is final
+    pub done: bool,
+    pub snapshot: Snapshot,
+}
+
+/// Takes the assignment, and a sender for when the worker is ready to start it.
+type Assign = oneshot::Sender<(Assignment, oneshot::Sender<()>)>;
+
+struct WorkerState {
+    assignment: Mutex<Option<Assign>>,
+    start: Mutex<Option<oneshot::Sender<Start>>>,
+    done: AtomicBool,
+    shutdown: Notify,
+}
+
+/// A worker's api for the coordinator:
+///
+/// * `POST /assign` with an [`Assignment`] prepares the run, and answers once the worker is ready
+///   to start it, after populating. Only the first one is accepted.
+/// * `POST /start` with a [`Start`] schedules the start.
+/// * `GET /report` returns a [`WorkerReport`].
+/// * `POST /shutdown` lets the worker exit once it is done.
+pub struct Worker {
+    state: Arc<WorkerState>,
+    server: tokio::task::JoinHandle<()>,
+    ready: Option<oneshot::Sender<()>>,
+    start: Option<oneshot::Receiver<Start>>,
+}
+impl Worker {
+    /// Serve the worker api and wait for a coordinator to assign this worker its share.
+    pub async fn start(listen: SocketAddr) -> (Self, Assignment) {
+        let (assign, assigned) = oneshot::channel();
+        let (start, started) = oneshot::channel();
+        let state = Arc::new(WorkerState {
+            assignment: Mutex::new(Some(assign)),
+            start: Mutex::new(Some(start)),
+            done: AtomicBool::new(false),
+            shutdown: Notify::new(),
+        });
+        let listener = tokio::net::TcpListener::bind(listen)
+            .await
+            .expect("must be able to bind the worker listener");
+        log::info!("worker waiting for a coordinator on http://{listen}");
+        let app = Router::new()
+            .route("/assign", post(assign_run))
+            .route("/start", post(start_run))
+            .route("/report", get(report))
+            .route("/shutdown", post(shutdown))
+            .with_state(state.clone());
+        let server = tokio::spawn({
+            let state = state.clone();
+            async move {
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(async move { state.shutdown.notified().await })
+                    .await
+                    .expect("worker endpoint must not fail");
+            }
+        });
+        let (assignment, ready) = assigned
+            .await
+            .expect("the worker server must be running until it is assigned");
+        log::info!("assigned {assignment:?}");
+        let worker = Self {
+            state,
+            server,
+            ready: Some(ready),
+            start: Some(started),
+        };
+        (worker, assignment)
+    }
+
+    /// Tell the coordinator this worker is ready, like after populating, and wait until the time
+    /// every worker starts together.
+    pub async fn ready(&mut self) {
+        if let Some(ready) = self.ready.take() {
+            // The coordinator may have given up waiting, and it will find out on /start
+            let _ = ready.send(());
+        }
+        log::info!("worker ready, waiting for the coordinator to start the run");
+        let start = self
+            .start
+            .take()
+            .expect("a worker starts once")
+            .await
+            .expect("the worker server must be running until it is started");
+        wait_until(start.start_at_ms).await;
+    }
+
+    /// Report the final snapshot to the coordinator, and wait until it lets this worker go.
+    pub async fn finish(self) {
+        // Release, so a report that sees done also sees every request recorded before it
+        self.state.done.store(true, Ordering::Release);
+        log::info!("worker finished, waiting for the coordinator to collect the report");
+        tokio::select! {
+            _ = self.server => {}
+            _ = tokio::signal::ctrl_c() => log::info!("interrupted"),
+        }
+    }
+}
+
+async fn assign_run(
+    State(state): State<Arc<WorkerState>>,
+    Json(assignment): Json<Assignment>,
+) -> StatusCode {
+    let Some(assign) = state.assignment.lock().expect("local mutex works").take() else {
+        return StatusCode::CONFLICT;
+    };
+    let (ready, is_ready) = oneshot::channel();
+    if assign.send((assignment, ready)).is_err() {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    match is_ready.await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+async fn start_run(State(state): State<Arc<WorkerState>>, Json(start): Json<Start>) -> StatusCode {
+    let Some(start_run) = state.start.lock().expect("local mutex works").take() else {
+        return StatusCode::CONFLICT;
+    };
+    match start_run.send(start) {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+async fn report(State(state): State<Arc<WorkerState>>) -> Json<WorkerReport> {
+    // Read done first, with Acquire to pair with the Release in finish, so a done report never
+    // misses the last requests.
+    let done = state.done.load(Ordering::Acquire);
+    Json(WorkerReport {
+        done,
+        snapshot: local_metrics::default_registry().snapshot(),
+    })
+}
+
+async fn shutdown(State(state): State<Arc<WorkerState>>) -> StatusCode {
+    state.shutdown.notify_one();
+    StatusCode::ACCEPTED
+}
+
+/// Sleep until a unix time in milliseconds. Hosts need synchronized clocks to start together.
+pub async fn wait_until(unix_ms: u64) {
+    let now = results::unix_millis();
+    if unix_ms < now {
+        log::warn!("the start time passed {}ms ago", now - unix_ms);
+        return;
+    }
+    tokio::time::sleep(Duration::from_millis(unix_ms - now)).await;
+}
@@ -0,0 +1,137 @@
+//! Runs a coordinator and two workers against the local stand-in, and checks that the merged
+//! report has every request the stand-in served. The processes share no metrics, like on
+//! separate hosts.
+
+use std::{
+    net::SocketAddr,
+    process::Stdio,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{extract::Request, middleware::Next};
+use tokio::process::Command;
+
+/// An address nothing is listening on yet.
+fn free_address() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .expect("must bind a local port")
+        .local_addr()
+        .expect("must have an address")
+}
+
+fn tester(endpoint: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ddb-load-tester"));
+    command
+        .env("AWS_ACCESS_KEY_ID", "AKID")
+        .env("AWS_SECRET_ACCESS_KEY", "secret")
+        .env("AWS_REGION", "us-west-2")
+        .env("AWS_ENDPOINT_URL", endpoint)
+        .env_remove("RUST_LOG")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    command
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merged_report_counts_every_request() {
+    // Slow responses keep requests in flight when the run ends
+    let served = Arc::new(AtomicU64::new(0));
+    let app = ddb_local::router(ddb_local::Config {
+        latency: Duration::from_millis(300),
+        ..ddb_local::Config::default()
+    })
+    .layer(axum::middleware::from_fn({
+        let served = served.clone();
+        move |request: Request, next: Next| {
+            let served = served.clone();
+            async move {
+                served.fetch_add(1, Ordering::Relaxed);
+                next.run(request).await
+            }
+        }
+    }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("must bind a local port");
+    let endpoint = format!(
+        "http://{}",
+        listener.local_addr().expect("must have an address")
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let workers = [free_address(), free_address()];
+    let mut worker_processes = Vec::new();
+    for worker in workers {
+        worker_processes.push(
+            tester(&endpoint)
+                .args(["--worker-listen", &worker.to_string()])
+                .args(["--retry-mode", "disabled"])
+                .spawn()
+                .expect("must start a worker"),
+        );
+    }
+    for worker in workers {
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while tokio::net::TcpStream::connect(worker).await.is_err() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("the workers must start listening");
+    }
+
+    let results_dir = std::env::temp_dir().join(format!("ddb-distributed-{}", std::process::id()));
+    let status = tester(&endpoint)
+        .args([
+            "--coordinate",
+            &format!("{},{}", workers[0], workers[1]),
+            "--duration",
+            "2",
+            "--tps",
+            "40",
+            "--mix",
+            "put",
+            "--items",
+            "100",
+            "--start-delay-ms",
+            "500",
+            "--results-dir",
+        ])
+        .arg(&results_dir)
+        .status()
+        .await
+        .expect("must run the coordinator");
+    assert!(status.success(), "the coordinator failed: {status}");
+    for mut worker in worker_processes {
+        let status = tokio::time::timeout(Duration::from_secs(30), worker.wait())
+            .await
+            .expect("the coordinator must let the workers go")
+            .expect("must wait for a worker");
+        assert!(status.success(), "a worker failed: {status}");
+    }
+
+    let summary: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(results_dir.join("summary.json")).expect("must write a summary"),
+    )
+    .expect("the summary is json");
+    std::fs::remove_dir_all(&results_dir).expect("must clean up the results");
+    let requests = summary["overall"]["requests"]
+        .as_u64()
+        .expect("the summary counts requests");
+    assert_eq!(summary["overall"]["errors"].as_u64(), Some(0));
+    assert!(40 <= requests, "only {requests} requests were sent");
+    assert_eq!(requests, served.load(Ordering::Relaxed));
+    // The run, without the wait for the last slow requests
+    let duration = summary["duration_seconds"]
+        .as_f64()
+        .expect("the summary has a duration");
+    assert!(
+        (2.0..2.25).contains(&duration),
+        "the summary covers {duration}s"
+    );
+}
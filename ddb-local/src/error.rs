@@ -0,0 +1,67 @@
+use axum::{
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+
+/// An error in the shape DynamoDB returns it, so the SDK parses it into the same error variant.
+#[derive(Debug)]
+pub struct DynamoError {
+    status: StatusCode,
+    kind: &'static str,
+    message: String,
+}
+impl DynamoError {
+    pub fn new(status: StatusCode, kind: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "ValidationException", message)
+    }
+
+    pub fn serialization(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "SerializationException", message)
+    }
+
+    pub fn conditional_check_failed() -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "ConditionalCheckFailedException",
+            "The conditional request failed",
+        )
+    }
+
+    pub fn resource_not_found(table: &str) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "ResourceNotFoundException",
+            format!("Requested resource not found: Table: {table} not found"),
+        )
+    }
+
+    pub fn unknown_operation(target: &str) -> Self {
+        Self::new(
+            StatusCode::BAD_REQUEST,
+            "UnknownOperationException",
+            format!("{target} is not supported by the local stand-in"),
+        )
+    }
+}
+impl IntoResponse for DynamoError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({
+            "__type": format!("com.amazonaws.dynamodb.v20120810#{}", self.kind),
+            "message": self.message,
+        });
+        (
+            self.status,
+            [(header::CONTENT_TYPE, crate::CONTENT_TYPE)],
+            body.to_string(),
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,623 @@
+//! The subset of DynamoDB's expression language the stand-in understands: conditions, updates
+//! with `SET` and `REMOVE`, and projections, on top-level attributes only.
+
+use std::cmp::Ordering;
+
+use serde_json::{Map, Value};
+
+use crate::error::DynamoError;
+
+pub type Item = Map<String, Value>;
+
+/// Resolves `#name` and `:value` placeholders from a request's `ExpressionAttributeNames` and
+/// `ExpressionAttributeValues`.
+#[derive(Clone, Copy)]
+pub struct Placeholders<'a> {
+    names: Option<&'a Map<String, Value>>,
+    values: Option<&'a Map<String, Value>>,
+}
+impl<'a> Placeholders<'a> {
+    pub fn new(request: &'a Value) -> Self {
+        Self {
+            names: request
+                .get("ExpressionAttributeNames")
+                .and_then(Value::as_object),
+            values: request
+                .get("ExpressionAttributeValues")
+                .and_then(Value::as_object),
+        }
+    }
+
+    fn name(&self, placeholder: &str) -> Result<String, DynamoError> {
+        self.names
+            .and_then(|names| names.get(placeholder))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                DynamoError::validation(format!(
+                    "An expression attribute name used in the document path is not defined; attribute name: {placeholder}"
+                ))
+            })
+    }
+
+    fn value(&self, placeholder: &str) -> Result<Value, DynamoError> {
+        self.values
+            .and_then(|values| values.get(placeholder))
+            .cloned()
+            .ok_or_else(|| {
+                DynamoError::validation(format!(
+                    "An expression attribute value used in expression is not defined; attribute value: {placeholder}"
+                ))
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Path(String),
+    Value(Value),
+}
+impl Operand {
+    fn resolve<'a>(&'a self, item: &'a Item) -> Option<&'a Value> {
+        match self {
+            Operand::Path(name) => item.get(name),
+            Operand::Value(value) => Some(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    Compare(Operand, Comparator, Operand),
+    Between(Operand, Operand, Operand),
+    In(Operand, Vec<Operand>),
+    Exists(String),
+    NotExists(String),
+    BeginsWith(Operand, Operand),
+    Contains(Operand, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+impl Condition {
+    pub fn parse(expression: &str, placeholders: Placeholders) -> Result<Self, DynamoError> {
+        let mut parser = Parser::new(expression, placeholders)?;
+        let condition = parser.condition()?;
+        parser.end()?;
+        Ok(condition)
+    }
+
+    pub fn evaluate(&self, item: &Item) -> bool {
+        match self {
+            Condition::Compare(left, comparator, right) => {
+                let (Some(left), Some(right)) = (left.resolve(item), right.resolve(item)) else {
+                    return false;
+                };
+                match comparator {
+                    Comparator::Eq => equal(left, right),
+                    Comparator::Ne => !equal(left, right),
+                    Comparator::Lt => compare(left, right) == Some(Ordering::Less),
+                    Comparator::Le => compare(left, right).is_some_and(Ordering::is_le),
+                    Comparator::Gt => compare(left, right) == Some(Ordering::Greater),
+                    Comparator::Ge => compare(left, right).is_some_and(Ordering::is_ge),
+                }
+            }
+            Condition::Between(operand, low, high) => {
+                match (operand.resolve(item), low.resolve(item), high.resolve(item)) {
+                    (Some(operand), Some(low), Some(high)) => {
+                        compare(operand, low).is_some_and(Ordering::is_ge)
+                            && compare(operand, high).is_some_and(Ordering::is_le)
+                    }
+                    _ => false,
+                }
+            }
+            Condition::In(operand, candidates) => operand.resolve(item).is_some_and(|operand| {
+                candidates
+                    .iter()
+                    .filter_map(|candidate| candidate.resolve(item))
+                    .any(|candidate| equal(operand, candidate))
+            }),
+            Condition::Exists(name) => item.contains_key(name),
+            Condition::NotExists(name) => !item.contains_key(name),
+            Condition::BeginsWith(operand, prefix) => {
+                match (
+                    operand.resolve(item).and_then(string),
+                    prefix.resolve(item).and_then(string),
+                ) {
+                    (Some(operand), Some(prefix)) => operand.starts_with(prefix),
+                    _ => false,
+                }
+            }
+            Condition::Contains(operand, part) => match (operand.resolve(item), part.resolve(item))
+            {
+                (Some(operand), Some(part)) => contains(operand, part),
+                _ => false,
+            },
+            Condition::And(left, right) => left.evaluate(item) && right.evaluate(item),
+            Condition::Or(left, right) => left.evaluate(item) || right.evaluate(item),
+            Condition::Not(condition) => !condition.evaluate(item),
+        }
+    }
+
+    /// The value a condition requires `name` to equal, like the partition key of a key condition.
+    pub fn equality(&self, name: &str) -> Option<&Value> {
+        match self {
+            Condition::Compare(Operand::Path(path), Comparator::Eq, Operand::Value(value))
+            | Condition::Compare(Operand::Value(value), Comparator::Eq, Operand::Path(path))
+                if path == name =>
+            {
+                Some(value)
+            }
+            Condition::And(left, right) => left.equality(name).or_else(|| right.equality(name)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Operand(Operand),
+    IfNotExists(String, Operand),
+}
+impl Term {
+    fn resolve(&self, item: &Item) -> Result<Value, DynamoError> {
+        let value = match self {
+            Term::Operand(operand) => operand.resolve(item),
+            Term::IfNotExists(name, operand) => item.get(name).or_else(|| operand.resolve(item)),
+        };
+        value.cloned().ok_or_else(|| {
+            DynamoError::validation(
+                "The provided expression refers to an attribute that does not exist in the item",
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ValueExpression {
+    Term(Term),
+    Plus(Term, Term),
+    Minus(Term, Term),
+}
+impl ValueExpression {
+    fn evaluate(&self, item: &Item) -> Result<Value, DynamoError> {
+        let (left, right, sign) = match self {
+            ValueExpression::Term(term) => return term.resolve(item),
+            ValueExpression::Plus(left, right) => (left, right, 1.0),
+            ValueExpression::Minus(left, right) => (left, right, -1.0),
+        };
+        match (number(&left.resolve(item)?), number(&right.resolve(item)?)) {
+            (Some(left), Some(right)) => Ok(number_value(left + sign * right)),
+            _ => Err(DynamoError::validation(
+                "An operand in the update expression has an incorrect data type",
+            )),
+        }
+    }
+}
+
+/// A parsed `UpdateExpression`.
+#[derive(Debug, Clone)]
+pub struct Update {
+    set: Vec<(String, ValueExpression)>,
+    remove: Vec<String>,
+}
+impl Update {
+    pub fn parse(expression: &str, placeholders: Placeholders) -> Result<Self, DynamoError> {
+        let mut parser = Parser::new(expression, placeholders)?;
+        let mut update = Update {
+            set: Vec::new(),
+            remove: Vec::new(),
+        };
+        while !parser.at_end() {
+            match parser.keyword()?.as_str() {
+                "SET" => loop {
+                    let name = parser.path()?;
+                    parser.expect(&Token::Operator("="))?;
+                    let left = parser.term()?;
+                    let value = if parser.eat(&Token::Operator("+")) {
+                        ValueExpression::Plus(left, parser.term()?)
+                    } else if parser.eat(&Token::Operator("-")) {
+                        ValueExpression::Minus(left, parser.term()?)
+                    } else {
+                        ValueExpression::Term(left)
+                    };
+                    update.set.push((name, value));
+                    if !parser.eat(&Token::Comma) {
+                        break;
+                    }
+                },
+                "REMOVE" => loop {
+                    update.remove.push(parser.path()?);
+                    if !parser.eat(&Token::Comma) {
+                        break;
+                    }
+                },
+                other => {
+                    return Err(DynamoError::validation(format!(
+                        "Invalid UpdateExpression: {other} is not supported by the local stand-in"
+                    )));
+                }
+            }
+        }
+        if update.set.is_empty() && update.remove.is_empty() {
+            return Err(DynamoError::validation(
+                "Invalid UpdateExpression: The expression can not be empty",
+            ));
+        }
+        Ok(update)
+    }
+
+    /// The attribute names this update changes.
+    pub fn attributes(&self) -> impl Iterator<Item = &str> {
+        self.set
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .chain(self.remove.iter().map(String::as_str))
+    }
+
+    /// Apply the update. Every value is computed from the item as it was before the update.
+    pub fn apply(&self, item: &mut Item) -> Result<(), DynamoError> {
+        let values = self
+            .set
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), value.evaluate(item)?)))
+            .collect::<Result<Vec<_>, DynamoError>>()?;
+        item.extend(values);
+        for name in &self.remove {
+            item.remove(name);
+        }
+        Ok(())
+    }
+}
+
+/// The attribute names of a `ProjectionExpression`.
+pub fn projection(
+    expression: &str,
+    placeholders: Placeholders,
+) -> Result<Vec<String>, DynamoError> {
+    let mut parser = Parser::new(expression, placeholders)?;
+    let mut names = vec![parser.path()?];
+    while parser.eat(&Token::Comma) {
+        names.push(parser.path()?);
+    }
+    parser.end()?;
+    Ok(names)
+}
+
+/// Whether two attribute values are equal. Numbers are equal by value, like `1` and `1.0`.
+pub fn equal(left: &Value, right: &Value) -> bool {
+    match (number(left), number(right)) {
+        (Some(left), Some(right)) => left == right,
+        _ => left == right,
+    }
+}
+
+/// The order of two strings or two numbers, which are the values that can be ordered.
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    if let (Some(left), Some(right)) = (number(left), number(right)) {
+        return left.partial_cmp(&right);
+    }
+    match (string(left), string(right)) {
+        (Some(left), Some(right)) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
+
+/// Whether a string contains a substring, or a set or list contains a member.
+fn contains(operand: &Value, part: &Value) -> bool {
+    if let (Some(operand), Some(part)) = (string(operand), string(part)) {
+        return operand.contains(part);
+    }
+    match operand
+        .as_object()
+        .and_then(|operand| operand.iter().next())
+    {
+        Some((kind, Value::Array(members))) if kind == "L" => {
+            members.iter().any(|member| equal(member, part))
+        }
+        // Set members are bare strings, without their type
+        Some((_, Value::Array(members))) => {
+            let part = part.as_object().and_then(|part| part.values().next());
+            members.iter().any(|member| Some(member) == part)
+        }
+        _ => false,
+    }
+}
+
+fn string(value: &Value) -> Option<&str> {
+    value.get("S").and_then(Value::as_str)
+}
+
+pub fn number(value: &Value) -> Option<f64> {
+    value.get("N").and_then(Value::as_str)?.parse().ok()
+}
+
+fn number_value(number: f64) -> Value {
+    let number = if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        number.to_string()
+    };
+    serde_json::json!({ "N": number })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// An attribute name, with placeholders already resolved
+    Name(String),
+    /// A `:value` placeholder
+    Value(String),
+    /// A keyword or function name
+    Word(String),
+    Operator(&'static str),
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    placeholders: Placeholders<'a>,
+}
+impl<'a> Parser<'a> {
+    fn new(expression: &str, placeholders: Placeholders<'a>) -> Result<Self, DynamoError> {
+        let mut tokens = Vec::new();
+        let mut chars = expression.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            let token = match c {
+                '(' | ')' | ',' | '=' | '+' | '-' => {
+                    chars.next();
+                    match c {
+                        '(' => Token::LeftParen,
+                        ')' => Token::RightParen,
+                        ',' => Token::Comma,
+                        '=' => Token::Operator("="),
+                        '+' => Token::Operator("+"),
+                        _ => Token::Operator("-"),
+                    }
+                }
+                '<' | '>' => {
+                    chars.next();
+                    match (c, chars.peek()) {
+                        ('<', Some('>')) => {
+                            chars.next();
+                            Token::Operator("<>")
+                        }
+                        (_, Some('=')) => {
+                            chars.next();
+                            if c == '<' {
+                                Token::Operator("<=")
+                            } else {
+                                Token::Operator(">=")
+                            }
+                        }
+                        ('<', _) => Token::Operator("<"),
+                        _ => Token::Operator(">"),
+                    }
+                }
+                '#' | ':' | '_' | 'a'..='z' | 'A'..='Z' => {
+                    let mut word = String::new();
+                    word.push(c);
+                    chars.next();
+                    while let Some(&c) = chars.peek()
+                        && (c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        word.push(c);
+                        chars.next();
+                    }
+                    if matches!(chars.peek(), Some('.' | '[')) {
+                        return Err(DynamoError::validation(
+                            "Nested attribute paths are not supported by the local stand-in",
+                        ));
+                    }
+                    match c {
+                        '#' => Token::Name(placeholders.name(&word)?),
+                        ':' => Token::Value(word),
+                        _ => Token::Word(word),
+                    }
+                }
+                _ => {
+                    return Err(DynamoError::validation(format!(
+                        "Invalid expression: Syntax error; token: \"{c}\""
+                    )));
+                }
+            };
+            tokens.push(token);
+        }
+        Ok(Self {
+            tokens,
+            position: 0,
+            placeholders,
+        })
+    }
+
+    fn at_end(&self) -> bool {
+        self.tokens.len() <= self.position
+    }
+
+    fn end(&self) -> Result<(), DynamoError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(unexpected(token)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, DynamoError> {
+        let token = self.tokens.get(self.position).cloned().ok_or_else(|| {
+            DynamoError::validation("Invalid expression: Syntax error; unexpected end of input")
+        })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek() == Some(token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let matches =
+            matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), DynamoError> {
+        match self.next()? {
+            next if &next == token => Ok(()),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    fn keyword(&mut self) -> Result<String, DynamoError> {
+        match self.next()? {
+            Token::Word(word) => Ok(word.to_ascii_uppercase()),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    fn path(&mut self) -> Result<String, DynamoError> {
+        match self.next()? {
+            Token::Name(name) | Token::Word(name) => Ok(name),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, DynamoError> {
+        match self.next()? {
+            Token::Name(name) | Token::Word(name) => Ok(Operand::Path(name)),
+            Token::Value(placeholder) => Ok(Operand::Value(self.placeholders.value(&placeholder)?)),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, DynamoError> {
+        if matches!(self.peek(), Some(Token::Word(word)) if word == "if_not_exists") {
+            self.position += 1;
+            self.expect(&Token::LeftParen)?;
+            let name = self.path()?;
+            self.expect(&Token::Comma)?;
+            let default = self.operand()?;
+            self.expect(&Token::RightParen)?;
+            return Ok(Term::IfNotExists(name, default));
+        }
+        Ok(Term::Operand(self.operand()?))
+    }
+
+    fn condition(&mut self) -> Result<Condition, DynamoError> {
+        let mut condition = self.and()?;
+        while self.eat_keyword("OR") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, DynamoError> {
+        let mut condition = self.not()?;
+        while self.eat_keyword("AND") {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, DynamoError> {
+        if self.eat_keyword("NOT") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Condition, DynamoError> {
+        if self.eat(&Token::LeftParen) {
+            let condition = self.condition()?;
+            self.expect(&Token::RightParen)?;
+            return Ok(condition);
+        }
+        if let (Some(Token::Word(function)), Some(Token::LeftParen)) =
+            (self.peek(), self.tokens.get(self.position + 1))
+        {
+            let function = function.clone();
+            self.position += 2;
+            let condition = match function.as_str() {
+                "attribute_exists" => Condition::Exists(self.path()?),
+                "attribute_not_exists" => Condition::NotExists(self.path()?),
+                "begins_with" | "contains" => {
+                    let operand = self.operand()?;
+                    self.expect(&Token::Comma)?;
+                    let argument = self.operand()?;
+                    if function == "begins_with" {
+                        Condition::BeginsWith(operand, argument)
+                    } else {
+                        Condition::Contains(operand, argument)
+                    }
+                }
+                _ => {
+                    return Err(DynamoError::validation(format!(
+                        "Invalid expression: {function} is not supported by the local stand-in"
+                    )));
+                }
+            };
+            self.expect(&Token::RightParen)?;
+            return Ok(condition);
+        }
+        let left = self.operand()?;
+        if self.eat_keyword("BETWEEN") {
+            let low = self.operand()?;
+            if !self.eat_keyword("AND") {
+                return Err(DynamoError::validation(
+                    "Invalid expression: BETWEEN needs AND",
+                ));
+            }
+            return Ok(Condition::Between(left, low, self.operand()?));
+        }
+        if self.eat_keyword("IN") {
+            self.expect(&Token::LeftParen)?;
+            let mut candidates = vec![self.operand()?];
+            while self.eat(&Token::Comma) {
+                candidates.push(self.operand()?);
+            }
+            self.expect(&Token::RightParen)?;
+            return Ok(Condition::In(left, candidates));
+        }
+        let comparator = match self.next()? {
+            Token::Operator("=") => Comparator::Eq,
+            Token::Operator("<>") => Comparator::Ne,
+            Token::Operator("<") => Comparator::Lt,
+            Token::Operator("<=") => Comparator::Le,
+            Token::Operator(">") => Comparator::Gt,
+            Token::Operator(">=") => Comparator::Ge,
+            other => return Err(unexpected(&other)),
+        };
+        Ok(Condition::Compare(left, comparator, self.operand()?))
+    }
+}
+
+fn unexpected(token: &Token) -> DynamoError {
+    DynamoError::validation(format!(
+        "Invalid expression: Syntax error; token: {token:?}"
+    ))
+}
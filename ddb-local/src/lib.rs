@@ -0,0 +1,191 @@
+//! A local stand-in for DynamoDB, for testing the load tester and the lambda without AWS.
+//!
+//! It speaks DynamoDB's JSON 1.0 protocol for GetItem, PutItem, UpdateItem, DeleteItem, Query,
+//! Scan, BatchGetItem and BatchWriteItem, and keeps items in memory. Expressions are supported on
+//! top-level attributes, and indexes are not. It can add latency, fail requests, and leave batch
+//! items unprocessed, to see how clients cope. With credentials, it checks request signatures
+//! like DynamoDB does.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use rand::Rng;
+use serde_json::Value;
+
+use crate::{error::DynamoError, operations::Tables, table::Table};
+
+mod error;
+mod expression;
+mod operations;
+mod sigv4;
+mod table;
+
+pub use sigv4::Credentials;
+pub use table::TableSchema;
+
+const CONTENT_TYPE: &str = "application/x-amz-json-1.0";
+const TARGET_PREFIX: &str = "DynamoDB_20120810.";
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The tables to serve, empty at first
+    pub tables: Vec<TableSchema>,
+    /// Added to every request
+    pub latency: Duration,
+    /// Up to this much more is added to every request, uniformly
+    pub latency_jitter: Duration,
+    /// Fraction of requests that fail with an `InternalServerError`
+    pub error_rate: f64,
+    /// Fraction of requests that fail with a `ProvisionedThroughputExceededException`
+    pub throttle_rate: f64,
+    /// Fraction of batch items that are returned unprocessed
+    pub unprocessed_rate: f64,
+    /// Reject requests that aren't signed with these, or were changed after they were signed.
+    /// Without credentials, signatures aren't checked
+    pub credentials: Option<Credentials>,
+}
+impl Default for Config {
+    /// The load tester's `users` table, without faults.
+    fn default() -> Self {
+        Self {
+            tables: vec![TableSchema::new("users", "user", None)],
+            latency: Duration::ZERO,
+            latency_jitter: Duration::ZERO,
+            error_rate: 0.0,
+            throttle_rate: 0.0,
+            unprocessed_rate: 0.0,
+            credentials: None,
+        }
+    }
+}
+
+struct LocalState {
+    config: Config,
+    tables: Mutex<Tables>,
+}
+
+/// The stand-in's routes, to serve or to nest in another router.
+pub fn router(config: Config) -> Router {
+    for rate in [
+        config.error_rate,
+        config.throttle_rate,
+        config.unprocessed_rate,
+    ] {
+        assert!((0.0..=1.0).contains(&rate), "rates must be from 0 to 1");
+    }
+    let tables = config
+        .tables
+        .iter()
+        .map(|schema| (schema.name.clone(), Table::new(schema.clone())))
+        .collect();
+    // Every path is served like DynamoDB does, so a changed path fails its signature check
+    Router::new()
+        .route("/", post(handle))
+        .route("/{*path}", post(handle))
+        .with_state(Arc::new(LocalState {
+            tables: Mutex::new(tables),
+            config,
+        }))
+}
+
+/// Serve the stand-in until the listener fails.
+pub async fn serve(listener: tokio::net::TcpListener, config: Config) -> std::io::Result<()> {
+    axum::serve(listener, router(config)).await
+}
+
+async fn handle(
+    State(state): State<Arc<LocalState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let config = &state.config;
+    let (delay, fault) = {
+        let mut random = rand::rng();
+        let jitter = random.random_range(0.0..=1.0) * config.latency_jitter.as_secs_f64();
+        let roll: f64 = random.random();
+        (config.latency + Duration::from_secs_f64(jitter), roll)
+    };
+    tokio::time::sleep(delay).await;
+
+    let verified = match &config.credentials {
+        Some(credentials) => sigv4::verify(credentials, &method, &uri, &headers, &body),
+        None => Ok(()),
+    };
+    let response = if let Err(e) = verified {
+        Err(e)
+    } else if fault < config.error_rate {
+        Err(DynamoError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "InternalServerError",
+            "Injected internal server error",
+        ))
+    } else if fault < config.error_rate + config.throttle_rate {
+        Err(DynamoError::new(
+            StatusCode::BAD_REQUEST,
+            "ProvisionedThroughputExceededException",
+            "Injected throttling",
+        ))
+    } else {
+        dispatch(&state, &headers, &body)
+    };
+    let request_id = format!("{:032x}", rand::rng().random::<u128>());
+    let mut response = match response {
+        Ok(body) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            body.to_string(),
+        )
+            .into_response(),
+        Err(e) => {
+            log::debug!("{} failed: {e:?}", operation(&headers).unwrap_or("request"));
+            e.into_response()
+        }
+    };
+    response.headers_mut().insert(
+        "x-amzn-requestid",
+        request_id.parse().expect("hex is a valid header value"),
+    );
+    response
+}
+
+fn dispatch(state: &LocalState, headers: &HeaderMap, body: &[u8]) -> Result<Value, DynamoError> {
+    let operation = operation(headers)
+        .ok_or_else(|| DynamoError::unknown_operation("a request without x-amz-target"))?;
+    let request: Value =
+        serde_json::from_slice(body).map_err(|e| DynamoError::serialization(e.to_string()))?;
+    let unprocessed_rate = state.config.unprocessed_rate;
+    let mut tables = state.tables.lock().expect("local mutex works");
+    let tables = &mut *tables;
+    match operation {
+        "GetItem" => operations::get_item(tables, &request),
+        "PutItem" => operations::put_item(tables, &request),
+        "UpdateItem" => operations::update_item(tables, &request),
+        "DeleteItem" => operations::delete_item(tables, &request),
+        "Query" => operations::query(tables, &request),
+        "Scan" => operations::scan(tables, &request),
+        "BatchGetItem" => operations::batch_get_item(tables, &request, unprocessed_rate),
+        "BatchWriteItem" => operations::batch_write_item(tables, &request, unprocessed_rate),
+        other => Err(DynamoError::unknown_operation(other)),
+    }
+}
+
+/// The api call named by the `x-amz-target` header, like `GetItem`.
+fn operation(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-amz-target")?
+        .to_str()
+        .ok()?
+        .strip_prefix(TARGET_PREFIX)
+}
@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::DynamoError,
+    expression::{Condition, Item, Placeholders, Update, projection},
+    table::{Key, KeyValue, Table, item_size},
+};
+
+/// DynamoDB's limits on items per batch
+const MAX_BATCH_GET_ITEMS: usize = 100;
+const MAX_BATCH_WRITE_ITEMS: usize = 25;
+
+pub type Tables = HashMap<String, Table>;
+
+pub fn get_item(tables: &mut Tables, request: &Value) -> Result<Value, DynamoError> {
+    let table = table(tables, request, "TableName")?;
+    let key = table.key(object(request, "Key")?)?;
+    let projection = projection_of(request)?;
+    let consistent = flag(request, "ConsistentRead", false);
+    let item = table.get(&key);
+    let mut response = Map::new();
+    let units = read_units(item.map_or(0, item_size), consistent);
+    if let Some(item) = item {
+        response.insert("Item".into(), Value::Object(project(item, &projection)));
+    }
+    insert_capacity(&mut response, request, &table.schema.name, units);
+    Ok(Value::Object(response))
+}
+
+pub fn put_item(tables: &mut Tables, request: &Value) -> Result<Value, DynamoError> {
+    let table = table(tables, request, "TableName")?;
+    let item = object(request, "Item")?.clone();
+    let key = table.item_key(&item)?;
+    check_condition(request, table.get(&key))?;
+    let size = item_size(&item);
+    let old = table.put(item)?;
+    let mut response = Map::new();
+    let units = write_units(size.max(old.as_ref().map_or(0, item_size)));
+    if let (Some(old), "ALL_OLD") = (old, return_values(request)) {
+        response.insert("Attributes".into(), Value::Object(old));
+    }
+    insert_capacity(&mut response, request, &table.schema.name, units);
+    Ok(Value::Object(response))
+}
+
+pub fn update_item(tables: &mut Tables, request: &Value) -> Result<Value, DynamoError> {
+    let table = table(tables, request, "TableName")?;
+    let key_attributes = object(request, "Key")?;
+    let key = table.key(key_attributes)?;
+    let update = match request.get("UpdateExpression").and_then(Value::as_str) {
+        Some(expression) => Some(Update::parse(expression, Placeholders::new(request))?),
+        None => None,
+    };
+    if let Some(update) = &update
+        && let Some(name) = update
+            .attributes()
+            .find(|name| key_attributes.contains_key(*name))
+    {
+        return Err(DynamoError::validation(format!(
+            "One or more parameter values were invalid: Cannot update attribute {name}. This attribute is part of the key"
+        )));
+    }
+    let old = table.get(&key).cloned();
+    check_condition(request, old.as_ref())?;
+    let mut item = old.clone().unwrap_or_else(|| key_attributes.clone());
+    if let Some(update) = &update {
+        update.apply(&mut item)?;
+    }
+    let units = write_units(item_size(&item).max(old.as_ref().map_or(0, item_size)));
+    let updated = |item: &Item| -> Item {
+        let names: Vec<&str> = update.iter().flat_map(Update::attributes).collect();
+        item.iter()
+            .filter(|(name, _)| names.contains(&name.as_str()))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    };
+    let attributes = match return_values(request) {
+        "ALL_OLD" => old.clone(),
+        "UPDATED_OLD" => old.as_ref().map(updated),
+        "ALL_NEW" => Some(item.clone()),
+        "UPDATED_NEW" => Some(updated(&item)),
+        _ => None,
+    };
+    table.put(item)?;
+    let mut response = Map::new();
+    if let Some(attributes) = attributes {
+        response.insert("Attributes".into(), Value::Object(attributes));
+    }
+    insert_capacity(&mut response, request, &table.schema.name, units);
+    Ok(Value::Object(response))
+}
+
+pub fn delete_item(tables: &mut Tables, request: &Value) -> Result<Value, DynamoError> {
+    let table = table(tables, request, "TableName")?;
+    let key = table.key(object(request, "Key")?)?;
+    check_condition(request, table.get(&key))?;
+    let old = table.delete(&key);
+    let mut response = Map::new();
+    let units = write_units(old.as_ref().map_or(0, item_size));
+    if let (Some(old), "ALL_OLD") = (old, return_values(request)) {
+        response.insert("Attributes".into(), Value::Object(old));
+    }
+    insert_capacity(&mut response, request, &table.schema.name, units);
+    Ok(Value::Object(response))
+}
+
+pub fn query(tables: &mut Tables, request: &Value) -> Result<Value, DynamoError> {
+    let table = table(tables, request, "TableName")?;
+    if request.get("IndexName").is_some() {
+        return Err(DynamoError::validation(
+            "Indexes are not supported by the local stand-in",
+        ));
+    }
+    let key_condition = Condition::parse(
+        request
+            .get("KeyConditionExpression")
+            .and_then(Value::as_str)
+            .ok_or_else(|| DynamoError::validation("KeyConditionExpression is required"))?,
+        Placeholders::new(request),
+    )?;
+    let partition_key = &table.schema.partition_key;
+    let partition = key_condition.equality(partition_key).ok_or_else(|| {
+        DynamoError::validation(format!(
+            "Query condition missed key schema element: {partition_key}"
+        ))
+    })?;
+    let partition = KeyValue::parse(partition_key, partition)?;
+    let start = exclusive_start_key(table, request)?.map(|(_, sort)| sort);
+    let forward = flag(request, "ScanIndexForward", true);
+    let items = table
+        .partition(&partition, forward, start)
+        .filter(|item| key_condition.evaluate(item));
+    page(table, request, items)
+}
+
+pub fn scan(tables: &mut Tables, request: &Value) -> Result<Value, DynamoError> {
+    let table = table(tables, request, "TableName")?;
+    let segment = request.get("Segment").and_then(Value::as_u64);
+    let total_segments = request.get("TotalSegments").and_then(Value::as_u64);
+    let segment = match (segment, total_segments) {
+        (None, None) => None,
+        (Some(segment), Some(total)) if segment < total => Some((segment, total)),
+        _ => {
+            return Err(DynamoError::validation(
+                "Segment must be set with TotalSegments, and be less than it",
+            ));
+        }
+    };
+    let start = exclusive_start_key(table, request)?;
+    let items = table
+        .items(start)
+        .filter(|(partition, _)| {
+            segment.is_none_or(|(segment, total)| stable_hash(partition) % total == segment)
+        })
+        .map(|(_, item)| item);
+    page(table, request, items)
+}
+
+pub fn batch_get_item(
+    tables: &mut Tables,
+    request: &Value,
+    unprocessed_rate: f64,
+) -> Result<Value, DynamoError> {
+    let request_items = object(request, "RequestItems")?;
+    let total: usize = request_items
+        .values()
+        .map(|keys| {
+            keys.get("Keys")
+                .and_then(Value::as_array)
+                .map_or(0, Vec::len)
+        })
+        .sum();
+    if MAX_BATCH_GET_ITEMS < total {
+        return Err(DynamoError::validation(
+            "Too many items requested for the BatchGetItem call",
+        ));
+    }
+    // Check every table and key before reading, so an invalid key fails the whole batch
+    // without marking earlier keys unprocessed
+    let mut batches = Vec::with_capacity(request_items.len());
+    for (name, keys_and_attributes) in request_items {
+        let table = tables
+            .get(name)
+            .ok_or_else(|| DynamoError::resource_not_found(name))?;
+        let projection = projection_of(keys_and_attributes)?;
+        let keys = keys_and_attributes
+            .get("Keys")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|key| {
+                let key_attributes = key
+                    .as_object()
+                    .ok_or_else(|| DynamoError::serialization("Keys must be maps"))?;
+                Ok((key, table.key(key_attributes)?))
+            })
+            .collect::<Result<Vec<_>, DynamoError>>()?;
+        batches.push((name, keys_and_attributes, table, projection, keys));
+    }
+    let mut random = rand::rng();
+    let (mut responses, mut unprocessed, mut capacity) = (Map::new(), Map::new(), Vec::new());
+    for (name, keys_and_attributes, table, projection, keys) in batches {
+        let consistent = flag(keys_and_attributes, "ConsistentRead", false);
+        let (mut items, mut skipped, mut units) = (Vec::new(), Vec::new(), 0.0);
+        for (key, parsed) in keys {
+            if random.random_bool(unprocessed_rate) {
+                skipped.push(key.clone());
+                continue;
+            }
+            let item = table.get(&parsed);
+            units += read_units(item.map_or(0, item_size), consistent);
+            items.extend(item.map(|item| Value::Object(project(item, &projection))));
+        }
+        responses.insert(name.clone(), Value::Array(items));
+        if !skipped.is_empty() {
+            let mut keys_and_attributes = keys_and_attributes.clone();
+            keys_and_attributes["Keys"] = Value::Array(skipped);
+            unprocessed.insert(name.clone(), keys_and_attributes);
+        }
+        capacity.push(json!({ "TableName": name, "CapacityUnits": units }));
+    }
+    let mut response = Map::from_iter([
+        ("Responses".to_string(), Value::Object(responses)),
+        ("UnprocessedKeys".to_string(), Value::Object(unprocessed)),
+    ]);
+    if returns_capacity(request) {
+        response.insert("ConsumedCapacity".into(), Value::Array(capacity));
+    }
+    Ok(Value::Object(response))
+}
+
+pub fn batch_write_item(
+    tables: &mut Tables,
+    request: &Value,
+    unprocessed_rate: f64,
+) -> Result<Value, DynamoError> {
+    let request_items = object(request, "RequestItems")?;
+    let total: usize = request_items
+        .values()
+        .map(|writes| writes.as_array().map_or(0, Vec::len))
+        .sum();
+    if MAX_BATCH_WRITE_ITEMS < total {
+        return Err(DynamoError::validation(
+            "Too many items requested for the BatchWriteItem call",
+        ));
+    }
+    // Check every table and write before writing, because DynamoDB applies none of a batch
+    // with an invalid write in it
+    let mut batches = Vec::with_capacity(request_items.len());
+    for (name, writes) in request_items {
+        let table = tables
+            .get(name)
+            .ok_or_else(|| DynamoError::resource_not_found(name))?;
+        let writes = writes
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|write| Ok((write, Write::parse(table, write)?)))
+            .collect::<Result<Vec<_>, DynamoError>>()?;
+        batches.push((name, writes));
+    }
+    let mut random = rand::rng();
+    let (mut unprocessed, mut capacity) = (Map::new(), Vec::new());
+    for (name, writes) in batches {
+        let table = tables.get_mut(name).expect("the tables were checked");
+        let (mut skipped, mut units) = (Vec::new(), 0.0);
+        for (write, parsed) in writes {
+            if random.random_bool(unprocessed_rate) {
+                skipped.push(write.clone());
+                continue;
+            }
+            match parsed {
+                Write::Put(item) => {
+                    let old = table.put(item.clone())?;
+                    units += write_units(item_size(item).max(old.as_ref().map_or(0, item_size)));
+                }
+                Write::Delete(key) => {
+                    let old = table.delete(&key);
+                    units += write_units(old.as_ref().map_or(0, item_size));
+                }
+            }
+        }
+        if !skipped.is_empty() {
+            unprocessed.insert(name.clone(), Value::Array(skipped));
+        }
+        capacity.push(json!({ "TableName": name, "CapacityUnits": units }));
+    }
+    let mut response =
+        Map::from_iter([("UnprocessedItems".to_string(), Value::Object(unprocessed))]);
+    if returns_capacity(request) {
+        response.insert("ConsumedCapacity".into(), Value::Array(capacity));
+    }
+    Ok(Value::Object(response))
+}
+
+/// A checked write request of a BatchWriteItem.
+enum Write<'a> {
+    Put(&'a Item),
+    Delete(Key),
+}
+impl<'a> Write<'a> {
+    fn parse(table: &Table, write: &'a Value) -> Result<Self, DynamoError> {
+        if let Some(item) = write.pointer("/PutRequest/Item").and_then(Value::as_object) {
+            table.item_key(item)?;
+            Ok(Write::Put(item))
+        } else if let Some(key) = write
+            .pointer("/DeleteRequest/Key")
+            .and_then(Value::as_object)
+        {
+            Ok(Write::Delete(table.key(key)?))
+        } else {
+            Err(DynamoError::validation(
+                "A write request must have a PutRequest or a DeleteRequest",
+            ))
+        }
+    }
+}
+
+/// A page of a query or scan, up to `Limit` items, filtered by `FilterExpression`.
+fn page<'a>(
+    table: &Table,
+    request: &Value,
+    items: impl Iterator<Item = &'a Item>,
+) -> Result<Value, DynamoError> {
+    let filter = condition(request, "FilterExpression")?;
+    let projection = projection_of(request)?;
+    let limit = request
+        .get("Limit")
+        .and_then(Value::as_u64)
+        .map_or(usize::MAX, |limit| limit as usize);
+    let count_only = request.get("Select").and_then(Value::as_str) == Some("COUNT");
+    let mut items = items.peekable();
+    let (mut scanned, mut size, mut matched) = (0, 0, Vec::new());
+    let mut last = None;
+    while scanned < limit
+        && let Some(item) = items.next()
+    {
+        scanned += 1;
+        size += item_size(item);
+        last = Some(item);
+        if filter.as_ref().is_none_or(|filter| filter.evaluate(item)) {
+            matched.push(Value::Object(project(item, &projection)));
+        }
+    }
+    let mut response = Map::new();
+    response.insert("Count".into(), matched.len().into());
+    response.insert("ScannedCount".into(), scanned.into());
+    if !count_only {
+        response.insert("Items".into(), Value::Array(matched));
+    }
+    if let Some(last) = last
+        && items.peek().is_some()
+    {
+        response.insert(
+            "LastEvaluatedKey".into(),
+            Value::Object(table.key_attributes(last)),
+        );
+    }
+    let units = read_units(size, flag(request, "ConsistentRead", false));
+    insert_capacity(&mut response, request, &table.schema.name, units);
+    Ok(Value::Object(response))
+}
+
+fn exclusive_start_key(table: &Table, request: &Value) -> Result<Option<Key>, DynamoError> {
+    match request.get("ExclusiveStartKey") {
+        Some(Value::Object(key)) => table.key(key).map(Some),
+        Some(_) => Err(DynamoError::serialization(
+            "ExclusiveStartKey must be a map",
+        )),
+        None => Ok(None),
+    }
+}
+
+fn table<'a>(
+    tables: &'a mut Tables,
+    request: &Value,
+    field: &str,
+) -> Result<&'a mut Table, DynamoError> {
+    let name = request
+        .get(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| DynamoError::validation(format!("{field} is required")))?;
+    tables
+        .get_mut(name)
+        .ok_or_else(|| DynamoError::resource_not_found(name))
+}
+
+fn object<'a>(request: &'a Value, field: &str) -> Result<&'a Item, DynamoError> {
+    request
+        .get(field)
+        .and_then(Value::as_object)
+        .ok_or_else(|| DynamoError::validation(format!("{field} is required")))
+}
+
+fn flag(request: &Value, field: &str, default: bool) -> bool {
+    request
+        .get(field)
+        .and_then(Value::as_bool)
+        .unwrap_or(default)
+}
+
+fn return_values(request: &Value) -> &str {
+    request
+        .get("ReturnValues")
+        .and_then(Value::as_str)
+        .unwrap_or("NONE")
+}
+
+fn condition(request: &Value, field: &str) -> Result<Option<Condition>, DynamoError> {
+    request
+        .get(field)
+        .and_then(Value::as_str)
+        .map(|expression| Condition::parse(expression, Placeholders::new(request)))
+        .transpose()
+}
+
+/// Fail unless the item, or no item, meets the request's `ConditionExpression`.
+fn check_condition(request: &Value, item: Option<&Item>) -> Result<(), DynamoError> {
+    let Some(condition) = condition(request, "ConditionExpression")? else {
+        return Ok(());
+    };
+    let empty = Map::new();
+    if condition.evaluate(item.unwrap_or(&empty)) {
+        Ok(())
+    } else {
+        Err(DynamoError::conditional_check_failed())
+    }
+}
+
+fn projection_of(request: &Value) -> Result<Option<Vec<String>>, DynamoError> {
+    request
+        .get("ProjectionExpression")
+        .and_then(Value::as_str)
+        .map(|expression| projection(expression, Placeholders::new(request)))
+        .transpose()
+}
+
+fn project(item: &Item, projection: &Option<Vec<String>>) -> Item {
+    match projection {
+        None => item.clone(),
+        Some(names) => item
+            .iter()
+            .filter(|(name, _)| names.contains(name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect(),
+    }
+}
+
+fn returns_capacity(request: &Value) -> bool {
+    matches!(
+        request
+            .get("ReturnConsumedCapacity")
+            .and_then(Value::as_str),
+        Some("TOTAL" | "INDEXES")
+    )
+}
+
+fn insert_capacity(response: &mut Item, request: &Value, table: &str, units: f64) {
+    if returns_capacity(request) {
+        response.insert(
+            "ConsumedCapacity".into(),
+            json!({ "TableName": table, "CapacityUnits": units }),
+        );
+    }
+}
+
+/// A read unit per 4KB, or half of that for an eventually consistent read.
+fn read_units(size: usize, consistent: bool) -> f64 {
+    let units = size.div_ceil(4096).max(1) as f64;
+    if consistent { units } else { units / 2.0 }
+}
+
+/// A write unit per 1KB.
+fn write_units(size: usize) -> f64 {
+    size.div_ceil(1024).max(1) as f64
+}
+
+/// FNV-1a of a partition key, to split a scan into segments the same way every time.
+fn stable_hash(partition: &KeyValue) -> u64 {
+    let text = match partition {
+        KeyValue::S(s) | KeyValue::B(s) => s.clone(),
+        KeyValue::N(n) => n.to_string(),
+    };
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,254 @@
+//! Checks AWS Signature Version 4 the way DynamoDB does, so a request that was changed after it
+//! was signed is rejected here instead of only against AWS.
+
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::error::DynamoError;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+/// How far a request's signing time may be from now, like AWS allows
+const MAX_SKEW_SECONDS: i64 = 15 * 60;
+
+/// The static credentials requests must be signed with.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+impl Credentials {
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+        }
+    }
+}
+
+/// The parts of an `Authorization` header.
+struct Authorization<'a> {
+    access_key_id: &'a str,
+    /// date/region/service/aws4_request
+    scope: &'a str,
+    date: &'a str,
+    signed_headers: Vec<&'a str>,
+    signature: &'a str,
+}
+impl<'a> Authorization<'a> {
+    fn parse(header: &'a str) -> Option<Self> {
+        let fields = header.strip_prefix(ALGORITHM)?.trim();
+        let (mut credential, mut signed_headers, mut signature) = (None, None, None);
+        for field in fields.split(',').map(str::trim) {
+            match field.split_once('=')? {
+                ("Credential", value) => credential = Some(value),
+                ("SignedHeaders", value) => signed_headers = Some(value),
+                ("Signature", value) => signature = Some(value),
+                _ => (),
+            }
+        }
+        let (access_key_id, scope) = credential?.split_once('/')?;
+        Some(Self {
+            access_key_id,
+            scope,
+            date: scope.split('/').next()?,
+            signed_headers: signed_headers?.split(';').collect(),
+            signature: signature?,
+        })
+    }
+}
+
+/// Reject a request unless it is signed with `credentials` and unchanged since it was signed.
+pub fn verify(
+    credentials: &Credentials,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), DynamoError> {
+    let header = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| missing("Request is missing Authentication Token"))?;
+    let authorization = Authorization::parse(header).ok_or_else(|| {
+        missing(format!(
+            "Authorization header requires '{ALGORITHM}' with Credential, SignedHeaders and Signature"
+        ))
+    })?;
+    if authorization.access_key_id != credentials.access_key_id {
+        return Err(DynamoError::new(
+            StatusCode::BAD_REQUEST,
+            "UnrecognizedClientException",
+            "The security token included in the request is invalid.",
+        ));
+    }
+    if !authorization.signed_headers.contains(&"host") {
+        return Err(invalid_signature("'Host' must be a signed header."));
+    }
+    let timestamp = headers
+        .get("x-amz-date")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| missing("Request is missing X-Amz-Date"))?;
+    if !timestamp.starts_with(authorization.date) {
+        return Err(invalid_signature(
+            "Date in Credential scope does not match X-Amz-Date.",
+        ));
+    }
+    let signed_at = unix_seconds(timestamp)
+        .ok_or_else(|| invalid_signature(format!("Invalid X-Amz-Date {timestamp}")))?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("the clock is after 1970")
+        .as_secs() as i64;
+    if MAX_SKEW_SECONDS < (now - signed_at).abs() {
+        return Err(invalid_signature(format!(
+            "Signature expired: {timestamp} is more than 15 minutes from now."
+        )));
+    }
+
+    let payload_hash = hex::encode(Sha256::digest(body));
+    if let Some(declared) = headers.get("x-amz-content-sha256")
+        && declared.as_bytes() != payload_hash.as_bytes()
+    {
+        return Err(invalid_signature(
+            "The provided x-amz-content-sha256 header does not match what was computed.",
+        ));
+    }
+    let canonical_request = canonical_request(
+        method,
+        uri,
+        headers,
+        &authorization.signed_headers,
+        &payload_hash,
+    )?;
+    let string_to_sign = format!(
+        "{ALGORITHM}\n{timestamp}\n{}\n{}",
+        authorization.scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut key = format!("AWS4{}", credentials.secret_access_key).into_bytes();
+    for part in authorization.scope.split('/') {
+        key = hmac(&key, part.as_bytes());
+    }
+    let expected = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+    if !constant_time_equal(expected.as_bytes(), authorization.signature.as_bytes()) {
+        log::debug!("signature mismatch, canonical request:\n{canonical_request}");
+        return Err(invalid_signature(
+            "The request signature we calculated does not match the signature you provided. Check your AWS Secret Access Key and signing method. Consult the service documentation for details.",
+        ));
+    }
+    Ok(())
+}
+
+fn canonical_request(
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    signed_headers: &[&str],
+    payload_hash: &str,
+) -> Result<String, DynamoError> {
+    let path = uri
+        .path()
+        .split('/')
+        .map(encode)
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut query: Vec<&str> = uri
+        .query()
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter(|parameter| !parameter.is_empty())
+        .collect();
+    query.sort_unstable();
+
+    let mut canonical_headers = String::new();
+    for name in signed_headers {
+        let values = headers
+            .get_all(*name)
+            .iter()
+            .map(|value| {
+                let value = value.to_str().map_err(|_| {
+                    invalid_signature(format!("Header {name} is not a valid string"))
+                })?;
+                Ok(value.split_whitespace().collect::<Vec<_>>().join(" "))
+            })
+            .collect::<Result<Vec<_>, DynamoError>>()?;
+        if values.is_empty() {
+            return Err(invalid_signature(format!(
+                "Signed header {name} is missing from the request"
+            )));
+        }
+        canonical_headers.push_str(&format!("{name}:{}\n", values.join(",")));
+    }
+
+    Ok(format!(
+        "{method}\n{path}\n{}\n{canonical_headers}\n{}\n{payload_hash}",
+        query.join("&"),
+        signed_headers.join(";"),
+    ))
+}
+
+/// Percent-encode everything but unreserved characters. Paths are encoded again like this, since
+/// the SDK double-encodes paths for every service but S3.
+fn encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac takes keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn constant_time_equal(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0, |diff, (l, r)| diff | (l ^ r))
+            == 0
+}
+
+/// Seconds since the unix epoch of a `20240102T030405Z` timestamp.
+fn unix_seconds(timestamp: &str) -> Option<i64> {
+    let digits = |range: std::ops::Range<usize>| timestamp.get(range)?.parse::<i64>().ok();
+    if timestamp.len() != 16 || &timestamp[8..9] != "T" || !timestamp.ends_with('Z') {
+        return None;
+    }
+    let (year, month, day) = (digits(0..4)?, digits(4..6)?, digits(6..8)?);
+    let (hour, minute, second) = (digits(9..11)?, digits(11..13)?, digits(13..15)?);
+    // Days from civil, from Howard Hinnant's date algorithms
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+fn missing(message: impl Into<String>) -> DynamoError {
+    DynamoError::new(
+        StatusCode::BAD_REQUEST,
+        "MissingAuthenticationTokenException",
+        message,
+    )
+}
+
+fn invalid_signature(message: impl Into<String>) -> DynamoError {
+    DynamoError::new(
+        StatusCode::BAD_REQUEST,
+        "InvalidSignatureException",
+        message,
+    )
+}
@@ -0,0 +1,326 @@
+use std::{cmp::Ordering, collections::BTreeMap, str::FromStr};
+
+use serde_json::Value;
+
+use crate::{error::DynamoError, expression::Item};
+
+/// A table's name and key attributes, like `users:user` or `events:device:timestamp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSchema {
+    pub name: String,
+    pub partition_key: String,
+    pub sort_key: Option<String>,
+}
+impl TableSchema {
+    pub fn new(name: &str, partition_key: &str, sort_key: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            partition_key: partition_key.to_string(),
+            sort_key: sort_key.map(str::to_string),
+        }
+    }
+}
+impl FromStr for TableSchema {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(':').collect::<Vec<_>>()[..] {
+            [name, partition_key] => Ok(Self::new(name, partition_key, None)),
+            [name, partition_key, sort_key] => Ok(Self::new(name, partition_key, Some(sort_key))),
+            _ => Err(format!(
+                "expected name:partition_key or name:partition_key:sort_key, not {s}"
+            )),
+        }
+    }
+}
+
+/// A key attribute value, ordered the way a table orders its keys.
+#[derive(Debug, Clone)]
+pub enum KeyValue {
+    S(String),
+    N(Number),
+    /// Base64, compared as text rather than as bytes
+    B(String),
+}
+impl KeyValue {
+    pub fn parse(name: &str, value: &Value) -> Result<Self, DynamoError> {
+        let invalid = || {
+            DynamoError::validation(format!(
+                "One or more parameter values were invalid: Invalid key attribute {name}"
+            ))
+        };
+        let object = value.as_object().ok_or_else(invalid)?;
+        match object.iter().next() {
+            Some((kind, Value::String(value))) if object.len() == 1 => match kind.as_str() {
+                "S" => Ok(KeyValue::S(value.clone())),
+                "N" => value.parse().map(KeyValue::N).map_err(|_| invalid()),
+                "B" => Ok(KeyValue::B(value.clone())),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+impl PartialEq for KeyValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for KeyValue {}
+impl PartialOrd for KeyValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for KeyValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (KeyValue::S(left), KeyValue::S(right)) | (KeyValue::B(left), KeyValue::B(right)) => {
+                left.cmp(right)
+            }
+            (KeyValue::N(left), KeyValue::N(right)) => left.cmp(right),
+            // A key attribute has one type, so this only orders keys that failed validation
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+impl KeyValue {
+    fn rank(&self) -> u8 {
+        match self {
+            KeyValue::S(_) => 0,
+            KeyValue::N(_) => 1,
+            KeyValue::B(_) => 2,
+        }
+    }
+}
+
+/// A number key, normalized so that numbers that are equal are the same key however they are
+/// written. Like DynamoDB, it keeps up to 38 significant digits exactly rather than rounding them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Number {
+    negative: bool,
+    /// Without leading or trailing zeros, and empty for zero
+    digits: String,
+    /// The number is `0.digits` times 10 to this
+    exponent: i64,
+}
+impl FromStr for Number {
+    type Err = ();
+
+    /// Parse a DynamoDB number: decimal digits with an optional sign, point and exponent, in
+    /// DynamoDB's range of 1e-130 to under 1e126. `NaN` and `Infinity` aren't numbers here.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().map_err(|_| ())?),
+            None => (unsigned, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(());
+        }
+        let all = format!("{whole}{fraction}");
+        let digits = all.trim_matches('0');
+        if digits.is_empty() {
+            // -0 is 0
+            return Ok(Self {
+                negative: false,
+                digits: String::new(),
+                exponent: 0,
+            });
+        }
+        let leading_zeros = all.len() - all.trim_start_matches('0').len();
+        let exponent = exponent
+            .checked_add(whole.len() as i64 - leading_zeros as i64)
+            .ok_or(())?;
+        if 38 < digits.len() || !(-129..=126).contains(&exponent) {
+            return Err(());
+        }
+        Ok(Self {
+            negative,
+            digits: digits.to_string(),
+            exponent,
+        })
+    }
+}
+impl std::fmt::Display for Number {
+    /// The same text for numbers that are equal, like `-0.15e3` for -150.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        let sign = if self.negative { "-" } else { "" };
+        write!(f, "{sign}0.{}e{}", self.digits, self.exponent)
+    }
+}
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = |number: &Number| match (number.negative, number.digits.is_empty()) {
+            (_, true) => 0,
+            (true, false) => -1,
+            (false, false) => 1,
+        };
+        let magnitude = (self.exponent, &self.digits).cmp(&(other.exponent, &other.digits));
+        match sign(self).cmp(&sign(other)) {
+            Ordering::Equal if self.negative => magnitude.reverse(),
+            Ordering::Equal => magnitude,
+            different => different,
+        }
+    }
+}
+
+/// A partition key value, and the sort key value if the table has a sort key.
+pub type Key = (KeyValue, Option<KeyValue>);
+
+/// A table's items, in key order.
+pub struct Table {
+    pub schema: TableSchema,
+    partitions: BTreeMap<KeyValue, BTreeMap<Option<KeyValue>, Item>>,
+}
+impl Table {
+    pub fn new(schema: TableSchema) -> Self {
+        Self {
+            schema,
+            partitions: BTreeMap::new(),
+        }
+    }
+
+    /// The key of an item, which must have the key attributes.
+    pub fn item_key(&self, item: &Item) -> Result<Key, DynamoError> {
+        let attribute = |name: &str| {
+            let value = item.get(name).ok_or_else(|| {
+                DynamoError::validation(format!(
+                    "One or more parameter values were invalid: Missing the key {name} in the item"
+                ))
+            })?;
+            KeyValue::parse(name, value)
+        };
+        Ok((
+            attribute(&self.schema.partition_key)?,
+            self.schema.sort_key.as_deref().map(attribute).transpose()?,
+        ))
+    }
+
+    /// The key of a request's `Key`, which must have only the key attributes.
+    pub fn key(&self, key: &Item) -> Result<Key, DynamoError> {
+        let expected = 1 + usize::from(self.schema.sort_key.is_some());
+        if key.len() != expected {
+            return Err(DynamoError::validation(
+                "The provided key element does not match the schema",
+            ));
+        }
+        self.item_key(key)
+    }
+
+    /// Only the key attributes of an item, like for a `LastEvaluatedKey`.
+    pub fn key_attributes(&self, item: &Item) -> Item {
+        std::iter::once(&self.schema.partition_key)
+            .chain(&self.schema.sort_key)
+            .filter_map(|name| Some((name.clone(), item.get(name)?.clone())))
+            .collect()
+    }
+
+    pub fn get(&self, (partition, sort): &Key) -> Option<&Item> {
+        self.partitions.get(partition)?.get(sort)
+    }
+
+    /// Store an item, and return the item it replaced.
+    pub fn put(&mut self, item: Item) -> Result<Option<Item>, DynamoError> {
+        let (partition, sort) = self.item_key(&item)?;
+        Ok(self
+            .partitions
+            .entry(partition)
+            .or_default()
+            .insert(sort, item))
+    }
+
+    pub fn delete(&mut self, (partition, sort): &Key) -> Option<Item> {
+        let items = self.partitions.get_mut(partition)?;
+        let deleted = items.remove(sort);
+        if items.is_empty() {
+            self.partitions.remove(partition);
+        }
+        deleted
+    }
+
+    /// The items of a partition in sort key order, or in reverse, after `start` if there is one.
+    pub fn partition<'a>(
+        &'a self,
+        partition: &KeyValue,
+        forward: bool,
+        start: Option<Option<KeyValue>>,
+    ) -> Box<dyn Iterator<Item = &'a Item> + 'a> {
+        use std::ops::Bound::{Excluded, Unbounded};
+        let Some(items) = self.partitions.get(partition) else {
+            return Box::new(std::iter::empty());
+        };
+        match (forward, start) {
+            (true, None) => Box::new(items.values()),
+            (false, None) => Box::new(items.values().rev()),
+            (true, Some(start)) => Box::new(
+                items
+                    .range((Excluded(start), Unbounded))
+                    .map(|(_, item)| item),
+            ),
+            (false, Some(start)) => Box::new(
+                items
+                    .range((Unbounded, Excluded(start)))
+                    .rev()
+                    .map(|(_, item)| item),
+            ),
+        }
+    }
+
+    /// Every item in key order, after `start` if there is one.
+    pub fn items(&self, start: Option<Key>) -> impl Iterator<Item = (&KeyValue, &Item)> {
+        self.partitions
+            .iter()
+            .flat_map(|(partition, items)| {
+                items
+                    .iter()
+                    .map(move |(sort, item)| ((partition, sort), item))
+            })
+            .skip_while(move |((partition, sort), _)| {
+                start
+                    .as_ref()
+                    .is_some_and(|start| (*partition, *sort) <= (&start.0, &start.1))
+            })
+            .map(|((partition, _), item)| (partition, item))
+    }
+}
+
+/// Roughly the size DynamoDB bills an item for: its attribute names and values.
+pub fn item_size(item: &Item) -> usize {
+    item.iter()
+        .map(|(name, value)| name.len() + value_size(value))
+        .sum()
+}
+
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.len(),
+        Value::Array(values) => values.iter().map(value_size).sum::<usize>() + 3,
+        Value::Object(attributes) => attributes
+            .iter()
+            .map(|(kind, value)| match kind.as_str() {
+                "S" | "N" | "B" | "SS" | "NS" | "BS" | "L" => value_size(value),
+                "M" => value.as_object().map_or(0, |map| item_size(map) + 3),
+                _ => 1,
+            })
+            .sum(),
+        _ => 1,
+    }
+}
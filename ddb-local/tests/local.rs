@@ -0,0 +1,459 @@
+use aws_sdk_dynamodb::{
+    Client,
+    config::{BehaviorVersion, Credentials, Region, retry::RetryConfig},
+    error::ProvideErrorMetadata,
+    types::{AttributeValue, KeysAndAttributes, PutRequest, ReturnValue, WriteRequest},
+};
+use ddb_local::{Config, TableSchema};
+
+async fn start(config: Config) -> Client {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("must bind a local port");
+    let address = listener.local_addr().expect("must have an address");
+    tokio::spawn(ddb_local::serve(listener, config));
+    Client::from_conf(
+        aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-west-2"))
+            .credentials_provider(Credentials::new("AKID", "secret", None, None, "test"))
+            .endpoint_url(format!("http://{address}"))
+            .retry_config(RetryConfig::disabled())
+            .build(),
+    )
+}
+
+fn s(value: &str) -> AttributeValue {
+    AttributeValue::S(value.to_string())
+}
+
+fn n(value: i64) -> AttributeValue {
+    AttributeValue::N(value.to_string())
+}
+
+#[tokio::test]
+async fn put_get_and_delete() {
+    let client = start(Config::default()).await;
+    client
+        .put_item()
+        .table_name("users")
+        .item("user", s("alice"))
+        .item("value", s("test"))
+        .send()
+        .await
+        .expect("put must succeed");
+
+    let item = client
+        .get_item()
+        .table_name("users")
+        .key("user", s("alice"))
+        .send()
+        .await
+        .expect("get must succeed")
+        .item
+        .expect("the item must be there");
+    assert_eq!(item.get("value"), Some(&s("test")));
+
+    let deleted = client
+        .delete_item()
+        .table_name("users")
+        .key("user", s("alice"))
+        .return_values(ReturnValue::AllOld)
+        .send()
+        .await
+        .expect("delete must succeed");
+    assert_eq!(
+        deleted.attributes().and_then(|a| a.get("user")),
+        Some(&s("alice"))
+    );
+
+    let missing = client
+        .get_item()
+        .table_name("users")
+        .key("user", s("alice"))
+        .send()
+        .await
+        .expect("get must succeed");
+    assert!(missing.item.is_none());
+
+    let error = client
+        .get_item()
+        .table_name("nope")
+        .key("user", s("alice"))
+        .send()
+        .await
+        .expect_err("an unknown table must fail");
+    assert_eq!(error.code(), Some("ResourceNotFoundException"));
+}
+
+#[tokio::test]
+async fn conditional_updates() {
+    let client = start(Config::default()).await;
+    let update = |expected: Option<i64>| {
+        let request = client
+            .update_item()
+            .table_name("users")
+            .key("user", s("bob"))
+            .update_expression("SET #count = if_not_exists(#count, :zero) + :one, #version = :next")
+            .expression_attribute_names("#count", "count")
+            .expression_attribute_names("#version", "version")
+            .expression_attribute_values(":zero", n(0))
+            .expression_attribute_values(":one", n(1))
+            .expression_attribute_values(":next", n(expected.unwrap_or(0) + 1))
+            .return_values(ReturnValue::AllNew);
+        match expected {
+            Some(version) => request
+                .condition_expression("#version = :expected")
+                .expression_attribute_values(":expected", n(version)),
+            None => request.condition_expression("attribute_not_exists(#version)"),
+        }
+        .send()
+    };
+
+    let created = update(None).await.expect("the first update must succeed");
+    assert_eq!(
+        created.attributes().and_then(|a| a.get("count")),
+        Some(&n(1))
+    );
+    let updated = update(Some(1))
+        .await
+        .expect("the current version must succeed");
+    assert_eq!(
+        updated.attributes().and_then(|a| a.get("count")),
+        Some(&n(2))
+    );
+
+    let error = update(Some(1))
+        .await
+        .expect_err("a stale version must fail");
+    assert_eq!(error.code(), Some("ConditionalCheckFailedException"));
+    let error = update(None).await.expect_err("an existing item must fail");
+    assert_eq!(error.code(), Some("ConditionalCheckFailedException"));
+}
+
+#[tokio::test]
+async fn query_pages_through_a_partition() {
+    let client = start(Config {
+        tables: vec!["events:device:at".parse().expect("valid schema")],
+        ..Config::default()
+    })
+    .await;
+    for device in ["a", "b"] {
+        for at in 0..5 {
+            client
+                .put_item()
+                .table_name("events")
+                .item("device", s(device))
+                .item("at", n(at))
+                .send()
+                .await
+                .expect("put must succeed");
+        }
+    }
+
+    let query = |start, forward| {
+        client
+            .query()
+            .table_name("events")
+            .key_condition_expression("device = :device AND #at BETWEEN :from AND :to")
+            .expression_attribute_names("#at", "at")
+            .expression_attribute_values(":device", s("a"))
+            .expression_attribute_values(":from", n(1))
+            .expression_attribute_values(":to", n(4))
+            .limit(3)
+            .scan_index_forward(forward)
+            .set_exclusive_start_key(start)
+            .send()
+    };
+    for (forward, expected) in [(true, [1, 2, 3, 4]), (false, [4, 3, 2, 1])] {
+        let mut seen = Vec::new();
+        let mut start = None;
+        loop {
+            let page = query(start, forward).await.expect("query must succeed");
+            seen.extend(page.items().iter().map(|item| item["at"].clone()));
+            start = page.last_evaluated_key;
+            if start.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen, expected.map(n));
+    }
+}
+
+#[tokio::test]
+async fn number_keys_are_exact_decimals() {
+    let client = start(Config {
+        tables: vec!["events:device:at".parse().expect("valid schema")],
+        ..Config::default()
+    })
+    .await;
+    let number = |value: &str| AttributeValue::N(value.to_string());
+    let put = |at: &str| {
+        client
+            .put_item()
+            .table_name("events")
+            .item("device", s("a"))
+            .item("at", number(at))
+            .send()
+    };
+    // Apart only past the precision of an f64, and written differently than they are put
+    let at = [
+        "-1e125",
+        "-12345678901234567890123456789012345679",
+        "-12345678901234567890123456789012345678",
+        "-2.5",
+        "-0",
+        "0.0000001",
+        "1.5",
+        "99999999999999999999999999999999999998",
+        "99999999999999999999999999999999999999",
+    ];
+    for at in at {
+        put(at).await.expect("put must succeed");
+    }
+    let queried = client
+        .query()
+        .table_name("events")
+        .key_condition_expression("device = :device")
+        .expression_attribute_values(":device", s("a"))
+        .send()
+        .await
+        .expect("query must succeed");
+    assert_eq!(
+        queried
+            .items()
+            .iter()
+            .map(|item| item["at"].clone())
+            .collect::<Vec<_>>(),
+        at.map(number),
+        "distinct numbers are distinct keys, in numeric order"
+    );
+
+    // Equal numbers are the same key
+    put("1.50").await.expect("put must succeed");
+    put("0").await.expect("put must succeed");
+    for (equal, put) in [("15e-1", "1.50"), ("-0.0", "0")] {
+        let item = client
+            .get_item()
+            .table_name("events")
+            .key("device", s("a"))
+            .key("at", number(equal))
+            .send()
+            .await
+            .expect("get must succeed")
+            .item
+            .expect("the equal key finds the item");
+        assert_eq!(item["at"], number(put));
+    }
+
+    for invalid in [
+        "NaN",
+        "Infinity",
+        "-inf",
+        "1e126",
+        "1e-131",
+        "1234567890123456789012345678901234567890",
+        "1.2.3",
+        "",
+    ] {
+        let error = put(invalid).await.expect_err("the key is invalid");
+        assert_eq!(error.code(), Some("ValidationException"), "{invalid}");
+    }
+}
+
+#[tokio::test]
+async fn scan_segments_cover_every_item_once() {
+    let client = start(Config::default()).await;
+    for user in 0..20 {
+        client
+            .put_item()
+            .table_name("users")
+            .item("user", s(&format!("user-{user}")))
+            .send()
+            .await
+            .expect("put must succeed");
+    }
+    let mut seen = Vec::new();
+    for segment in 0..3 {
+        let page = client
+            .scan()
+            .table_name("users")
+            .segment(segment)
+            .total_segments(3)
+            .send()
+            .await
+            .expect("scan must succeed");
+        seen.extend(page.items().iter().map(|item| item["user"].clone()));
+    }
+    seen.sort_by_key(|user| user.as_s().expect("users are strings").clone());
+    let mut expected: Vec<_> = (0..20).map(|user| s(&format!("user-{user}"))).collect();
+    expected.sort_by_key(|user| user.as_s().expect("users are strings").clone());
+    assert_eq!(seen, expected);
+}
+
+#[tokio::test]
+async fn batches_return_unprocessed_items() {
+    let write = |client: Client| async move {
+        let writes = (0..10)
+            .map(|user| {
+                WriteRequest::builder()
+                    .put_request(
+                        PutRequest::builder()
+                            .item("user", s(&format!("user-{user}")))
+                            .build()
+                            .expect("complete put"),
+                    )
+                    .build()
+            })
+            .collect();
+        client
+            .batch_write_item()
+            .request_items("users", writes)
+            .send()
+            .await
+            .expect("batch write must succeed")
+    };
+    let get = |client: Client| async move {
+        let keys = KeysAndAttributes::builder()
+            .set_keys(Some(
+                (0..10)
+                    .map(|user| [("user".to_string(), s(&format!("user-{user}")))].into())
+                    .collect(),
+            ))
+            .build()
+            .expect("complete keys");
+        client
+            .batch_get_item()
+            .request_items("users", keys)
+            .send()
+            .await
+            .expect("batch get must succeed")
+    };
+
+    let client = start(Config::default()).await;
+    let written = write(client.clone()).await;
+    assert!(
+        written
+            .unprocessed_items()
+            .is_none_or(|items| items.is_empty())
+    );
+    let read = get(client).await;
+    assert_eq!(read.responses().map_or(0, |r| r["users"].len()), 10);
+
+    let client = start(Config {
+        unprocessed_rate: 1.0,
+        ..Config::default()
+    })
+    .await;
+    let written = write(client.clone()).await;
+    assert_eq!(
+        written.unprocessed_items().map_or(0, |r| r["users"].len()),
+        10
+    );
+    let read = get(client).await;
+    assert_eq!(
+        read.unprocessed_keys()
+            .map_or(0, |r| r["users"].keys().len()),
+        10
+    );
+}
+
+#[tokio::test]
+async fn batches_with_an_invalid_request_do_nothing() {
+    let put = |item: AttributeValue| {
+        WriteRequest::builder()
+            .put_request(
+                PutRequest::builder()
+                    .item("user", item)
+                    .build()
+                    .expect("complete put"),
+            )
+            .build()
+    };
+    let client = start(Config::default()).await;
+    let mut writes: Vec<_> = (0..5).map(|user| put(s(&format!("user-{user}")))).collect();
+    // A key attribute can't be a list
+    writes.push(put(AttributeValue::L(vec![])));
+    let error = client
+        .batch_write_item()
+        .request_items("users", writes)
+        .send()
+        .await
+        .expect_err("the last write is invalid");
+    assert_eq!(error.code(), Some("ValidationException"));
+    let scanned = client
+        .scan()
+        .table_name("users")
+        .send()
+        .await
+        .expect("scan must succeed");
+    assert_eq!(scanned.count(), 0, "nothing of the batch is written");
+
+    // Keys are checked even when every key would come back unprocessed
+    let client = start(Config {
+        unprocessed_rate: 1.0,
+        ..Config::default()
+    })
+    .await;
+    let mut keys: Vec<_> = (0..5)
+        .map(|user| [("user".to_string(), s(&format!("user-{user}")))].into())
+        .collect();
+    keys.push([("user".to_string(), AttributeValue::Bool(true))].into());
+    let error = client
+        .batch_get_item()
+        .request_items(
+            "users",
+            KeysAndAttributes::builder()
+                .set_keys(Some(keys))
+                .build()
+                .expect("complete keys"),
+        )
+        .send()
+        .await
+        .expect_err("the last key is invalid");
+    assert_eq!(error.code(), Some("ValidationException"));
+}
+
+#[tokio::test]
+async fn injected_errors() {
+    let client = start(Config {
+        error_rate: 1.0,
+        ..Config::default()
+    })
+    .await;
+    let error = client
+        .get_item()
+        .table_name("users")
+        .key("user", s("carol"))
+        .send()
+        .await
+        .expect_err("every request must fail");
+    assert_eq!(error.code(), Some("InternalServerError"));
+
+    let client = start(Config {
+        throttle_rate: 1.0,
+        ..Config::default()
+    })
+    .await;
+    let error = client
+        .put_item()
+        .table_name("users")
+        .item("user", s("carol"))
+        .send()
+        .await
+        .expect_err("every request must be throttled");
+    assert_eq!(error.code(), Some("ProvisionedThroughputExceededException"));
+}
+
+#[test]
+fn table_schemas_parse() {
+    assert_eq!(
+        "users:user".parse::<TableSchema>(),
+        Ok(TableSchema::new("users", "user", None))
+    );
+    assert_eq!(
+        "events:device:at".parse::<TableSchema>(),
+        Ok(TableSchema::new("events", "device", Some("at")))
+    );
+    assert!("users".parse::<TableSchema>().is_err());
+}
@@ -0,0 +1,177 @@
+use aws_sdk_dynamodb::{
+    Client,
+    config::{
+        BehaviorVersion, ConfigBag, Credentials, Intercept, Region, RuntimeComponents,
+        interceptors::BeforeTransmitInterceptorContextMut, retry::RetryConfig,
+    },
+    error::{BoxError, ProvideErrorMetadata},
+    types::AttributeValue,
+};
+use aws_smithy_types::body::SdkBody;
+use ddb_local::Config;
+
+const ACCESS_KEY_ID: &str = "AKIDLOCAL";
+const SECRET_ACCESS_KEY: &str = "local-secret";
+
+/// Changes a request after it was signed.
+#[derive(Debug, Clone, Copy)]
+enum Tamper {
+    Header,
+    Body,
+    Uri,
+}
+impl Intercept for Tamper {
+    fn name(&self) -> &'static str {
+        "Tamper"
+    }
+
+    fn modify_before_transmit(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let request = context.request_mut();
+        match self {
+            Tamper::Header => {
+                request
+                    .headers_mut()
+                    .insert("content-type", "application/x-amz-json-1.1");
+            }
+            Tamper::Body => {
+                let body = request
+                    .body()
+                    .bytes()
+                    .expect("bodies are in memory")
+                    .to_vec();
+                let body = String::from_utf8(body)
+                    .expect("bodies are json")
+                    .replace("alice", "alicf");
+                *request.body_mut() = SdkBody::from(body);
+            }
+            Tamper::Uri => {
+                let uri = request.uri().to_string() + "tampered";
+                *request.uri_mut() = uri.try_into().expect("must be a valid uri");
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn start() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("must bind a local port");
+    let address = listener.local_addr().expect("must have an address");
+    tokio::spawn(ddb_local::serve(
+        listener,
+        Config {
+            credentials: Some(ddb_local::Credentials::new(
+                ACCESS_KEY_ID,
+                SECRET_ACCESS_KEY,
+            )),
+            ..Config::default()
+        },
+    ));
+    format!("http://{address}")
+}
+
+fn client(endpoint: &str, access_key_id: &str, secret: &str, tamper: Option<Tamper>) -> Client {
+    let mut config = aws_sdk_dynamodb::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-west-2"))
+        .credentials_provider(Credentials::new(access_key_id, secret, None, None, "test"))
+        .endpoint_url(endpoint)
+        .retry_config(RetryConfig::disabled());
+    if let Some(tamper) = tamper {
+        config = config.interceptor(tamper);
+    }
+    Client::from_conf(config.build())
+}
+
+async fn get(client: &Client) -> Result<(), Option<String>> {
+    client
+        .get_item()
+        .table_name("users")
+        .key("user", AttributeValue::S("alice".to_string()))
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| e.code().map(str::to_string))
+}
+
+#[tokio::test]
+async fn signed_requests_succeed() {
+    let endpoint = start().await;
+    let client = client(&endpoint, ACCESS_KEY_ID, SECRET_ACCESS_KEY, None);
+    client
+        .put_item()
+        .table_name("users")
+        .item("user", AttributeValue::S("alice".to_string()))
+        .send()
+        .await
+        .expect("a signed put must succeed");
+    assert_eq!(get(&client).await, Ok(()));
+}
+
+#[tokio::test]
+async fn wrong_credentials_are_rejected() {
+    let endpoint = start().await;
+    let wrong_secret = client(&endpoint, ACCESS_KEY_ID, "not-the-secret", None);
+    assert_eq!(
+        get(&wrong_secret).await,
+        Err(Some("InvalidSignatureException".to_string()))
+    );
+    let unknown_key = client(&endpoint, "AKIDOTHER", SECRET_ACCESS_KEY, None);
+    assert_eq!(
+        get(&unknown_key).await,
+        Err(Some("UnrecognizedClientException".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn changes_after_signing_are_rejected() {
+    let endpoint = start().await;
+    for tamper in [Tamper::Header, Tamper::Body, Tamper::Uri] {
+        let client = client(&endpoint, ACCESS_KEY_ID, SECRET_ACCESS_KEY, Some(tamper));
+        assert_eq!(
+            get(&client).await,
+            Err(Some("InvalidSignatureException".to_string())),
+            "{tamper:?} must be rejected"
+        );
+    }
+}
+
+#[tokio::test]
+async fn unsigned_requests_are_rejected() {
+    let endpoint = start().await;
+    let response = unsigned_get(&endpoint).await;
+    assert!(
+        response.contains("MissingAuthenticationTokenException"),
+        "{response}"
+    );
+}
+
+/// A bare http request, without the SDK's signature.
+async fn unsigned_get(endpoint: &str) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let address = endpoint.strip_prefix("http://").expect("http endpoint");
+    let mut stream = tokio::net::TcpStream::connect(address)
+        .await
+        .expect("must connect");
+    let body = r#"{"TableName":"users","Key":{"user":{"S":"alice"}}}"#;
+    let request = format!(
+        "POST / HTTP/1.1\r\nhost: {address}\r\nx-amz-target: DynamoDB_20120810.GetItem\r\ncontent-type: application/x-amz-json-1.0\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .expect("must send");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("must receive");
+    response
+}
@@ -0,0 +1,174 @@
+//! How a signed DynamoDB request is carried to a proxy, and how the proxy gets it back.
+//!
+//! The client signs a request for DynamoDB, then sends it to the proxy instead. The original uri
+//! goes along in [`TARGET_URI_HEADER`]. Lambda function urls rewrite sigv4 headers, so for a lambda
+//! every header is renamed with [`RENAMED_PREFIX`] and [`decode`] restores them. [`VERSION_HEADER`]
+//! says which version of this protocol the client spoke, so a proxy can refuse one it doesn't know.
+//!
+//! Lambda can still merge, lowercase or drop some renamed headers, so [`encode_envelope`] packs
+//! the uri and every header into one base64 [`ENVELOPE_HEADER`] instead. [`decode`] reads either.
+//!
+//! Headers are `(name, value)` pairs with lowercase names, like the `http` crate gives them.
+//! Names are compared without regard to case, like http does.
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+/// The uri the request was signed for, where the proxy forwards it
+pub const TARGET_URI_HEADER: &str = "x-uri";
+/// The version of this protocol the client spoke
+pub const VERSION_HEADER: &str = "x-proxy-protocol";
+/// Put in front of every header name, so a lambda function url leaves the headers alone
+pub const RENAMED_PREFIX: &str = "hahaha-";
+/// The one header [`encode_envelope`] sends everything in
+pub const ENVELOPE_HEADER: &str = "x-proxy-envelope";
+/// The version [`encode`] and [`encode_envelope`] write
+pub const VERSION: u32 = 1;
+
+/// What [`encode_envelope`] sends, before it is json and base64.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    target_uri: String,
+    headers: Vec<(String, String)>,
+}
+
+/// The request a proxy should make for the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    /// Where the request was signed for
+    pub target_uri: String,
+    /// The request's headers as they were signed, plus any the client didn't send
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Without the target uri, the proxy doesn't know where to send the request
+    MissingTargetUri,
+    /// The client speaks a version of the protocol this proxy doesn't
+    UnsupportedVersion(String),
+    /// The envelope header isn't base64 json of an envelope
+    InvalidEnvelope(String),
+}
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::MissingTargetUri => write!(f, "missing {TARGET_URI_HEADER} header"),
+            DecodeError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported {VERSION_HEADER} {version}, this proxy speaks {VERSION}"
+            ),
+            DecodeError::InvalidEnvelope(e) => write!(f, "invalid {ENVELOPE_HEADER} header: {e}"),
+        }
+    }
+}
+impl std::error::Error for DecodeError {}
+
+/// The headers to send a signed request to a lambda proxy with: `headers` and the protocol
+/// headers, all renamed.
+pub fn encode(
+    target_uri: &str,
+    headers: impl IntoIterator<Item = (String, String)>,
+) -> Vec<(String, String)> {
+    headers
+        .into_iter()
+        .chain([
+            (TARGET_URI_HEADER.to_string(), target_uri.to_string()),
+            (VERSION_HEADER.to_string(), VERSION.to_string()),
+        ])
+        .map(|(name, value)| (format!("{RENAMED_PREFIX}{name}"), value))
+        .collect()
+}
+
+/// The one header to send a signed request to a lambda proxy with, holding `headers` and the
+/// target uri as base64 json. Lambda can't merge, lowercase or drop headers it can't see.
+pub fn encode_envelope(
+    target_uri: &str,
+    headers: impl IntoIterator<Item = (String, String)>,
+) -> (String, String) {
+    let envelope = Envelope {
+        version: VERSION,
+        target_uri: target_uri.to_string(),
+        headers: headers.into_iter().collect(),
+    };
+    let json = serde_json::to_vec(&envelope).expect("an envelope is always json");
+    (ENVELOPE_HEADER.to_string(), BASE64_STANDARD.encode(json))
+}
+
+/// Restore the request [`encode`] or [`encode_envelope`] sent. The signed headers replace any of
+/// the same name that were added on the way, like a `host` for the lambda. Renamed requests without
+/// a version are from before there was one, and are read as version 1.
+pub fn decode(headers: impl IntoIterator<Item = (String, String)>) -> Result<Decoded, DecodeError> {
+    let (envelope, headers): (Vec<_>, Vec<_>) = headers
+        .into_iter()
+        .partition(|(name, _)| name.eq_ignore_ascii_case(ENVELOPE_HEADER));
+    match envelope.into_iter().next_back() {
+        Some((_, envelope)) => decode_envelope(&envelope, headers),
+        None => decode_renamed(headers),
+    }
+}
+
+fn decode_envelope(envelope: &str, added: Vec<(String, String)>) -> Result<Decoded, DecodeError> {
+    let invalid = |e: &dyn std::fmt::Display| DecodeError::InvalidEnvelope(e.to_string());
+    let json = BASE64_STANDARD
+        .decode(envelope.trim())
+        .map_err(|e| invalid(&e))?;
+    let envelope: serde_json::Value = serde_json::from_slice(&json).map_err(|e| invalid(&e))?;
+    match envelope.get("version").and_then(serde_json::Value::as_u64) {
+        Some(version) if version == u64::from(VERSION) => (),
+        Some(version) => return Err(DecodeError::UnsupportedVersion(version.to_string())),
+        None => return Err(invalid(&"missing version")),
+    }
+    let envelope: Envelope = serde_json::from_value(envelope).map_err(|e| invalid(&e))?;
+    Ok(Decoded {
+        target_uri: envelope.target_uri,
+        headers: restore(added, envelope.headers),
+    })
+}
+
+fn decode_renamed(headers: Vec<(String, String)>) -> Result<Decoded, DecodeError> {
+    let (renamed, added): (Vec<_>, Vec<_>) = headers.into_iter().partition(|(name, _)| {
+        name.get(..RENAMED_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(RENAMED_PREFIX))
+    });
+    let renamed = renamed
+        .into_iter()
+        .map(|(name, value)| (name[RENAMED_PREFIX.len()..].to_string(), value))
+        .collect();
+    let mut headers = restore(added, renamed);
+
+    let mut take = |wanted: &str| {
+        let value = headers
+            .iter()
+            .rfind(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .map(|(_, value)| value.clone());
+        headers.retain(|(name, _)| !name.eq_ignore_ascii_case(wanted));
+        value
+    };
+    let version = take(VERSION_HEADER);
+    let target_uri = take(TARGET_URI_HEADER).ok_or(DecodeError::MissingTargetUri)?;
+    if let Some(version) = version
+        && version != VERSION.to_string()
+    {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    Ok(Decoded {
+        target_uri,
+        headers,
+    })
+}
+
+/// The headers from who knows where that the client didn't send, then the client's.
+fn restore(added: Vec<(String, String)>, signed: Vec<(String, String)>) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = added
+        .into_iter()
+        .filter(|(name, _)| {
+            !signed
+                .iter()
+                .any(|(signed, _)| signed.eq_ignore_ascii_case(name))
+        })
+        .collect();
+    headers.extend(signed);
+    headers
+}
@@ -0,0 +1,211 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use ddb_proxy_protocol::{
+    DecodeError, Decoded, ENVELOPE_HEADER, RENAMED_PREFIX, TARGET_URI_HEADER, VERSION_HEADER,
+    decode, encode, encode_envelope,
+};
+use proptest::prelude::*;
+
+/// Lowercase header names that aren't the protocol's own.
+fn name() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9-]{0,20}".prop_filter("not a protocol header", |name| {
+        !name.starts_with(RENAMED_PREFIX)
+            && ![TARGET_URI_HEADER, VERSION_HEADER, ENVELOPE_HEADER].contains(&name.as_str())
+    })
+}
+
+fn value() -> impl Strategy<Value = String> {
+    "[ -~]{0,40}"
+}
+
+fn headers() -> impl Strategy<Value = Vec<(String, String)>> {
+    prop::collection::vec((name(), value()), 0..12)
+}
+
+fn uri() -> impl Strategy<Value = String> {
+    "https://[a-z0-9.-]{1,30}(:[0-9]{1,5})?/[a-z0-9/]{0,20}"
+}
+
+proptest! {
+    #[test]
+    fn decode_restores_what_encode_sent(target_uri in uri(), headers in headers()) {
+        let decoded = decode(encode(&target_uri, headers.clone()));
+        prop_assert_eq!(decoded, Ok(Decoded { target_uri, headers }));
+    }
+
+    #[test]
+    fn every_encoded_header_is_renamed(target_uri in uri(), headers in headers()) {
+        for (name, _) in encode(&target_uri, headers) {
+            prop_assert!(name.starts_with(RENAMED_PREFIX));
+        }
+    }
+
+    #[test]
+    fn signed_headers_win_over_added_ones(
+        target_uri in uri(),
+        headers in headers(),
+        added in headers(),
+    ) {
+        let mut sent = added.clone();
+        sent.extend(encode(&target_uri, headers.clone()));
+        let decoded = decode(sent).expect("encoded requests decode");
+
+        prop_assert_eq!(&decoded.target_uri, &target_uri);
+        for (name, value) in &decoded.headers {
+            let expected: Vec<_> = if headers.iter().any(|(signed, _)| signed == name) {
+                headers.iter().filter(|(signed, _)| signed == name).collect()
+            } else {
+                added.iter().filter(|(added, _)| added == name).collect()
+            };
+            prop_assert!(expected.iter().any(|(_, expected)| expected == value));
+        }
+        prop_assert_eq!(
+            decoded.headers.len(),
+            headers.len() + added.iter().filter(|(name, _)| !headers.iter().any(|(signed, _)| signed == name)).count()
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn decode_restores_what_the_envelope_sent(
+        target_uri in uri(),
+        // Any visible ascii, so the envelope keeps what lambda would mangle
+        headers in prop::collection::vec(("[!-9;-~]{1,20}", value()), 0..12),
+        added in headers(),
+    ) {
+        let envelope = encode_envelope(&target_uri, headers.clone());
+        prop_assert_eq!(&envelope.0, ENVELOPE_HEADER);
+
+        let mut sent = added.clone();
+        sent.push(envelope);
+        let decoded = decode(sent).expect("envelopes decode");
+        prop_assert_eq!(decoded.target_uri, target_uri);
+        prop_assert!(decoded.headers.ends_with(&headers));
+        for (name, _) in &decoded.headers[..decoded.headers.len() - headers.len()] {
+            prop_assert!(!headers.iter().any(|(signed, _)| signed.eq_ignore_ascii_case(name)));
+        }
+    }
+}
+
+/// `name` with the letters `upper` picks uppercased, like a proxy that doesn't keep the case.
+fn recase(name: &str, upper: &[bool]) -> String {
+    name.chars()
+        .zip(upper.iter().cycle())
+        .map(|(c, upper)| if *upper { c.to_ascii_uppercase() } else { c })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn protocol_header_names_decode_in_any_case(
+        target_uri in uri(),
+        headers in headers(),
+        upper in prop::collection::vec(any::<bool>(), 1..8),
+    ) {
+        let renamed = encode(&target_uri, headers.clone())
+            .into_iter()
+            .map(|(name, value)| (recase(&name, &upper), value))
+            .collect::<Vec<_>>();
+        let decoded = decode(renamed).expect("recased requests decode");
+        prop_assert_eq!(&decoded.target_uri, &target_uri);
+        prop_assert_eq!(decoded.headers.len(), headers.len());
+        for ((name, value), (expected_name, expected_value)) in decoded.headers.iter().zip(&headers) {
+            prop_assert!(name.eq_ignore_ascii_case(expected_name));
+            prop_assert_eq!(value, expected_value);
+        }
+
+        let (name, envelope) = encode_envelope(&target_uri, headers.clone());
+        let decoded = decode([(recase(&name, &upper), envelope)]);
+        prop_assert_eq!(decoded, Ok(Decoded { target_uri, headers }));
+    }
+}
+
+#[test]
+fn names_that_split_a_character_at_the_prefix_are_not_renamed() {
+    // The prefix's length falls inside the é
+    assert_eq!(
+        decode([("hahahaé".to_string(), "value".to_string())]),
+        Err(DecodeError::MissingTargetUri)
+    );
+}
+
+#[test]
+fn envelopes_keep_every_value() {
+    let headers = vec![
+        (
+            "x-amz-target".to_string(),
+            "DynamoDB_20120810.GetItem".to_string(),
+        ),
+        ("X-Custom".to_string(), "a".to_string()),
+        ("X-Custom".to_string(), "b, c".to_string()),
+    ];
+    let decoded = decode([
+        ("host".to_string(), "lambda".to_string()),
+        ("x-custom".to_string(), "from lambda".to_string()),
+        encode_envelope("https://dynamodb/", headers.clone()),
+    ]);
+    // The signed X-Custom replaces the x-custom added on the way
+    let mut expected = vec![("host".to_string(), "lambda".to_string())];
+    expected.extend(headers);
+    assert_eq!(
+        decoded,
+        Ok(Decoded {
+            target_uri: "https://dynamodb/".to_string(),
+            headers: expected,
+        })
+    );
+}
+
+#[test]
+fn invalid_envelopes_fail() {
+    let envelope =
+        |json: &str| decode([(ENVELOPE_HEADER.to_string(), BASE64_STANDARD.encode(json))]);
+    assert_eq!(
+        envelope(r#"{"version":2,"target_uri":"https://dynamodb/","headers":[]}"#),
+        Err(DecodeError::UnsupportedVersion("2".to_string()))
+    );
+    assert!(matches!(
+        envelope(r#"{"target_uri":"https://dynamodb/","headers":[]}"#),
+        Err(DecodeError::InvalidEnvelope(_))
+    ));
+    assert!(matches!(
+        decode([(ENVELOPE_HEADER.to_string(), "not base64!".to_string())]),
+        Err(DecodeError::InvalidEnvelope(_))
+    ));
+}
+
+#[test]
+fn requests_without_a_version_are_version_1() {
+    let decoded = decode([
+        (
+            format!("{RENAMED_PREFIX}{TARGET_URI_HEADER}"),
+            "https://dynamodb/".to_string(),
+        ),
+        (
+            format!("{RENAMED_PREFIX}x-amz-target"),
+            "DynamoDB_20120810.GetItem".to_string(),
+        ),
+    ]);
+    assert_eq!(
+        decoded,
+        Ok(Decoded {
+            target_uri: "https://dynamodb/".to_string(),
+            headers: vec![(
+                "x-amz-target".to_string(),
+                "DynamoDB_20120810.GetItem".to_string()
+            )],
+        })
+    );
+}
+
+#[test]
+fn unknown_versions_and_missing_targets_fail() {
+    let mut headers = encode("https://dynamodb/", []);
+    headers.retain(|(name, _)| !name.ends_with(VERSION_HEADER));
+    headers.push((format!("{RENAMED_PREFIX}{VERSION_HEADER}"), "2".to_string()));
+    assert_eq!(
+        decode(headers),
+        Err(DecodeError::UnsupportedVersion("2".to_string()))
+    );
+    assert_eq!(decode([]), Err(DecodeError::MissingTargetUri));
+}